@@ -1,9 +1,72 @@
 mod account;
+mod account_activity;
+mod account_c;
+mod account_diff;
+mod account_key;
+mod account_store;
+mod aggregate_balances;
+mod amount_unit;
+mod bool_format;
+mod config;
+mod decimal_separator;
+mod dispute_state;
+mod engine_config;
+mod engine_error;
+mod engine_event;
+mod engine_stats;
+mod engine_warning;
+mod header_case;
+mod invalid_reason;
+#[cfg(feature = "iso20022")]
+mod iso20022_source;
+mod output_config;
+mod output_format;
+mod partial_chargeback_policy;
+mod record_source;
+mod reject_reason;
+mod snapshot;
 mod transaction_engine;
 mod transaction_record;
+mod transaction_status;
 mod transaction_type;
+mod tx_id;
+mod validation_error;
+mod withdrawal_dispute_policy;
+mod withdrawal_order;
+mod zero_sum_check;
 
-pub use self::account::{Account, TransactionDetails};
+pub use self::account::{Account, AccountOutput, TransactionDetails};
+pub use self::account_activity::AccountActivity;
+pub use self::account_c::AccountC;
+pub use self::account_diff::{diff_accounts, AccountDiff};
+pub use self::account_key::AccountKey;
+pub use self::account_store::{AccountHandle, AccountStore, InMemoryAccountStore};
+pub use self::aggregate_balances::AggregateBalances;
+pub use self::amount_unit::AmountUnit;
+pub use self::bool_format::BoolFormat;
+pub use self::config::Config;
+pub use self::decimal_separator::DecimalSeparator;
+pub use self::dispute_state::DisputeState;
+pub use self::engine_config::EngineConfig;
+pub use self::engine_error::EngineError;
+pub use self::engine_event::EngineEvent;
+pub use self::engine_stats::EngineStats;
+pub use self::engine_warning::EngineWarning;
+pub use self::header_case::HeaderCase;
+pub use self::invalid_reason::InvalidReason;
+#[cfg(feature = "iso20022")]
+pub use self::iso20022_source::Iso20022Source;
+pub use self::output_config::OutputConfig;
+pub use self::output_format::OutputFormat;
+pub use self::partial_chargeback_policy::PartialChargebackPolicy;
+pub use self::record_source::RecordSource;
+pub use self::reject_reason::RejectReason;
 pub use self::transaction_engine::TransactionEngine;
-pub use self::transaction_record::TransactionRecord;
+pub use self::transaction_record::{valid_records, validate_file, TransactionRecord};
+pub use self::transaction_status::TransactionStatus;
 pub use self::transaction_type::TransactionType;
+pub use self::tx_id::TxId;
+pub use self::validation_error::ValidationError;
+pub use self::withdrawal_dispute_policy::WithdrawalDisputePolicy;
+pub use self::withdrawal_order::WithdrawalOrder;
+pub use self::zero_sum_check::ZeroSumCheck;
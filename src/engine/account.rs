@@ -1,45 +1,323 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
-use serde::{ser::SerializeStruct, Serialize, Serializer};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
-#[derive(Debug, Clone)]
+use super::{AccountActivity, AccountKey, DisputeState, OutputConfig, TransactionType, TxId};
+
+/// Details of a recorded transaction.
+///
+/// Equality compares the raw `f32` amount, so it's only reliable for amounts
+/// that are exactly representable, like the ones used in tests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TransactionDetails {
+    pub tx_type: TransactionType,
     pub amount: f32,
-    pub disputed: bool,
+    /// Part of the amount that is currently held because of an open dispute.
+    pub held_amount: f32,
+    /// Part of the amount that has been charged back.
+    pub charged_back_amount: f32,
+    pub dispute_state: DisputeState,
 }
 
 impl TransactionDetails {
-    pub fn new(amount: f32) -> Self {
+    pub fn new(tx_type: TransactionType, amount: f32) -> Self {
         TransactionDetails {
+            tx_type,
             amount,
-            disputed: false,
+            held_amount: 0.0,
+            charged_back_amount: 0.0,
+            dispute_state: DisputeState::None,
         }
     }
+
+    /// Whether the transaction is currently disputed.
+    pub fn is_disputed(&self) -> bool {
+        self.dispute_state == DisputeState::Disputed
+    }
 }
 
+/// A client account.
+///
+/// Equality compares balances, lock state, ledger, metadata and activity, but not the insertion
+/// order of the ledger. Since balances are `f32`, only `PartialEq` is implemented and
+/// comparisons are exact, which makes them sensitive to rounding errors of accumulated amounts.
 #[derive(Debug, Clone)]
 pub struct Account {
+    pub tenant: Option<String>,
     pub client_id: u16,
     pub held_balance: f32,
     pub available_balance: f32,
     pub locked: bool,
-    pub transactions: HashMap<u32, TransactionDetails>,
+    pub transactions: HashMap<TxId, TransactionDetails>,
+    /// Name of the account holder, as seen in the first applied record.
+    pub name: Option<String>,
+    /// Currency of the account, as seen in the first applied record.
+    pub currency: Option<String>,
+    /// Transaction ids of the ledger, oldest first.
+    pub(super) ledger_order: VecDeque<TxId>,
+    /// Deposits and withdrawals applied so far, maintained by the engine.
+    pub(super) activity: AccountActivity,
+    /// Number of currently disputed transactions in the ledger, maintained by `set_dispute_state`.
+    pub(super) open_disputes: usize,
+}
+
+impl PartialEq for Account {
+    fn eq(&self, other: &Self) -> bool {
+        self.tenant == other.tenant
+            && self.client_id == other.client_id
+            && self.held_balance == other.held_balance
+            && self.available_balance == other.available_balance
+            && self.locked == other.locked
+            && self.transactions == other.transactions
+            && self.name == other.name
+            && self.currency == other.currency
+            && self.activity == other.activity
+    }
 }
 
 impl Account {
     pub fn new(client_id: u16) -> Self {
+        Self::from_key(AccountKey::new(None, client_id))
+    }
+
+    /// Construct a new account for the given key.
+    pub fn from_key(key: AccountKey) -> Self {
         Account {
-            client_id,
+            tenant: key.tenant,
+            client_id: key.client_id,
             held_balance: 0.0,
             available_balance: 0.0,
             locked: false,
             transactions: HashMap::new(),
+            name: None,
+            currency: None,
+            ledger_order: VecDeque::new(),
+            activity: AccountActivity::default(),
+            open_disputes: 0,
+        }
+    }
+
+    /// Return the key identifying this account.
+    pub fn key(&self) -> AccountKey {
+        AccountKey::new(self.tenant.clone(), self.client_id)
+    }
+
+    /// Record a transaction in the ledger.
+    pub fn record_transaction(&mut self, tx_id: TxId, details: TransactionDetails) {
+        if self.transactions.insert(tx_id, details).is_none() {
+            self.ledger_order.push_back(tx_id);
+        }
+    }
+
+    /// Evict the oldest undisputed entries while the ledger exceeds `max_entries`.
+    ///
+    /// Disputed entries are never evicted, so the ledger may stay above the limit
+    /// as long as all older entries are disputed.
+    /// Returns the evicted entries along with their positions in the ledger order, in eviction order.
+    pub fn evict_ledger_entries(
+        &mut self,
+        max_entries: usize,
+    ) -> Vec<(usize, TxId, TransactionDetails)> {
+        let mut evicted = Vec::new();
+        while self.transactions.len() > max_entries {
+            // Find the oldest undisputed entry
+            let transactions = &self.transactions;
+            let position = self.ledger_order.iter().position(|id| {
+                transactions
+                    .get(id)
+                    .is_some_and(|details| !details.is_disputed())
+            });
+
+            match position {
+                Some(position) => {
+                    if let Some(id) = self.ledger_order.remove(position) {
+                        if let Some(details) = self.transactions.remove(&id) {
+                            evicted.push((position, id, details));
+                        }
+                    }
+                }
+                None => break,
+            }
         }
+        evicted
+    }
+
+    /// Put an evicted entry back into the ledger at its previous position in the ledger order.
+    pub(super) fn restore_ledger_entry(
+        &mut self,
+        position: usize,
+        tx_id: TxId,
+        details: TransactionDetails,
+    ) {
+        self.transactions.insert(tx_id, details);
+        let position = position.min(self.ledger_order.len());
+        self.ledger_order.insert(position, tx_id);
+    }
+
+    /// Set the dispute state of the given transaction, keeping the number of open disputes up to date.
+    ///
+    /// Returns the updated ledger entry, or `None` if the transaction isn't in the ledger.
+    pub fn set_dispute_state(
+        &mut self,
+        tx_id: TxId,
+        state: DisputeState,
+    ) -> Option<&mut TransactionDetails> {
+        let details = self.transactions.get_mut(&tx_id)?;
+        match (details.is_disputed(), state == DisputeState::Disputed) {
+            (false, true) => self.open_disputes += 1,
+            (true, false) => self.open_disputes = self.open_disputes.saturating_sub(1),
+            _ => {}
+        }
+        details.dispute_state = state;
+        Some(details)
+    }
+
+    /// Remove a transaction from the ledger, returning its details if it was recorded.
+    ///
+    /// Entries should only be removed this way, so the ledger order stays in sync.
+    pub fn remove_transaction(&mut self, tx_id: TxId) -> Option<TransactionDetails> {
+        let details = self.transactions.remove(&tx_id)?;
+
+        // Rolled back entries are usually the most recent ones
+        if self.ledger_order.back() == Some(&tx_id) {
+            self.ledger_order.pop_back();
+        } else if let Some(position) = self.ledger_order.iter().position(|id| *id == tx_id) {
+            self.ledger_order.remove(position);
+        }
+        Some(details)
+    }
+
+    /// Return the id of the most recent deposit in the ledger that can currently be disputed.
+    pub fn latest_disputable_deposit(&self) -> Option<TxId> {
+        self.ledger_order.iter().rev().copied().find(|id| {
+            self.transactions.get(id).is_some_and(|details| {
+                details.tx_type == TransactionType::Deposit
+                    && details
+                        .dispute_state
+                        .transition(&TransactionType::Dispute)
+                        .is_some()
+            })
+        })
+    }
+
+    /// Draw the given amount from the held amounts of disputed transactions, oldest first,
+    /// e.g. when a withdrawal draws from held funds.
+    ///
+    /// This keeps later resolves and chargebacks from moving funds that are already gone.
+    pub fn draw_from_disputes(&mut self, mut amount: f32) {
+        let transactions = &mut self.transactions;
+        for id in &self.ledger_order {
+            if amount <= 0.0 {
+                break;
+            }
+            if let Some(details) = transactions
+                .get_mut(id)
+                .filter(|details| details.is_disputed())
+            {
+                let drawn = amount.min(details.held_amount);
+                details.held_amount -= drawn;
+                amount -= drawn;
+            }
+        }
+    }
+
+    /// Drop all entries of the ledger.
+    pub fn clear_ledger(&mut self) {
+        self.transactions.clear();
+        self.ledger_order.clear();
+        self.open_disputes = 0;
+    }
+
+    /// Return the deposits and withdrawals applied to the account so far.
+    pub fn activity(&self) -> AccountActivity {
+        self.activity
     }
 
     pub fn total_balance(&self) -> f32 {
         self.available_balance + self.held_balance
     }
+
+    /// Move the given amount from the available to the held balance, or back if it's negative.
+    ///
+    /// Both balances are updated in a single call, so the total balance never changes
+    /// and no state with only one of the balances updated is ever observable.
+    /// Returns `false` without changing the account if either balance would overflow.
+    pub fn transition_held(&mut self, delta: f32) -> bool {
+        let available_balance = self.available_balance - delta;
+        let held_balance = self.held_balance + delta;
+        if !available_balance.is_finite() || !held_balance.is_finite() {
+            return false;
+        }
+        self.available_balance = available_balance;
+        self.held_balance = held_balance;
+        true
+    }
+
+    /// Check whether the account is solvent, i.e. its total balance isn't negative.
+    pub fn is_solvent(&self) -> bool {
+        self.total_balance() >= 0.0
+    }
+
+    /// Return the number of currently disputed transactions.
+    ///
+    /// This is a counter maintained by `set_dispute_state`, so it doesn't scan the ledger.
+    /// Dispute states changed directly on `transactions` aren't counted.
+    pub fn open_disputes(&self) -> usize {
+        self.open_disputes
+    }
+
+    /// Recount the currently disputed transactions of the ledger, e.g. after loading it.
+    pub(super) fn recount_open_disputes(&mut self) {
+        self.open_disputes = self
+            .transactions
+            .values()
+            .filter(|tx| tx.is_disputed())
+            .count();
+    }
+
+    /// Pair the account with an `OutputConfig` for serialization.
+    pub fn output<'a>(&'a self, config: &'a OutputConfig) -> AccountOutput<'a> {
+        AccountOutput {
+            account: self,
+            config,
+        }
+    }
+}
+
+/// Format a balance with the given number of decimal places.
+///
+/// Fixed-precision formatting is locale-independent and never uses scientific notation,
+/// so the output always consists of an optional sign, digits and a `.` separator.
+fn format_balance(value: f32, precision: usize) -> String {
+    format!("{:.*}", precision, value)
+}
+
+/// Parse a formatted balance into integer units of its last decimal place.
+///
+/// Returns `None` if the balance doesn't fit.
+fn balance_units(balance: &str) -> Option<i128> {
+    balance.replace('.', "").parse().ok()
+}
+
+/// Format integer units of the given number of decimal places as a balance.
+fn format_units(units: i128, precision: usize) -> String {
+    let digits = format!("{:0width$}", units.unsigned_abs(), width = precision + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - precision);
+    let sign = if units < 0 { "-" } else { "" };
+    if precision == 0 {
+        format!("{}{}", sign, integer)
+    } else {
+        format!("{}{}.{}", sign, integer, fraction)
+    }
+}
+
+/// An `Account` serialized according to an `OutputConfig`.
+///
+/// Balances are always serialized as fixed-precision strings, never as numbers,
+/// so formats like JSON keep the exact decimals even for consumers parsing numbers as floats.
+pub struct AccountOutput<'a> {
+    account: &'a Account,
+    config: &'a OutputConfig,
 }
 
 impl Serialize for Account {
@@ -47,12 +325,413 @@ impl Serialize for Account {
     where
         S: Serializer,
     {
-        let mut state = serializer.serialize_struct("Account", 5)?;
-        state.serialize_field("client", &self.client_id)?;
-        state.serialize_field("available", &self.available_balance)?;
-        state.serialize_field("held", &self.held_balance)?;
-        state.serialize_field("total", &self.total_balance())?;
-        state.serialize_field("locked", &self.locked)?;
+        self.output(&OutputConfig::default()).serialize(serializer)
+    }
+}
+
+impl<'a> Serialize for AccountOutput<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let account = self.account;
+        let config = self.config;
+        let scale = |precision: Option<usize>| precision.unwrap_or(config.display_scale);
+        let field_count = 5
+            + usize::from(config.with_solvency)
+            + 2 * usize::from(config.with_metadata)
+            + 4 * usize::from(config.with_activity);
+        let available_precision = scale(config.available_precision);
+        let held_precision = scale(config.held_precision);
+        let total_precision = scale(config.total_precision);
+        let available = format_balance(account.available_balance, available_precision);
+        let held = format_balance(account.held_balance, held_precision);
+
+        // Sum up the displayed balances if possible, so the total always matches them exactly.
+        // Recomputing the total in `f32` may be off by one in the last decimal place.
+        let same_precision =
+            available_precision == total_precision && held_precision == total_precision;
+        let total = match (balance_units(&available), balance_units(&held)) {
+            (Some(available), Some(held)) if same_precision => {
+                format_units(available + held, total_precision)
+            }
+            _ => format_balance(account.total_balance(), total_precision),
+        };
+
+        let mut state = serializer.serialize_struct("Account", field_count)?;
+        state.serialize_field("client", &account.client_id)?;
+        state.serialize_field("available", &available)?;
+        state.serialize_field("held", &held)?;
+        state.serialize_field("total", &total)?;
+        state.serialize_field("locked", config.locked_format.format(account.locked))?;
+        if config.with_solvency {
+            state.serialize_field("solvent", config.locked_format.format(account.is_solvent()))?;
+        }
+        if config.with_metadata {
+            state.serialize_field("client_name", &account.name)?;
+            state.serialize_field("currency", &account.currency)?;
+        }
+        if config.with_activity {
+            let activity = account.activity;
+            state.serialize_field("deposit_count", &activity.deposit_count)?;
+            state.serialize_field("withdrawal_count", &activity.withdrawal_count)?;
+            state.serialize_field(
+                "total_deposited",
+                &format_balance(activity.total_deposited, config.display_scale),
+            )?;
+            state.serialize_field(
+                "total_withdrawn",
+                &format_balance(activity.total_withdrawn, config.display_scale),
+            )?;
+        }
         state.end()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Account, OutputConfig, TransactionDetails, TransactionType};
+    use crate::engine::{BoolFormat, DisputeState, TxId};
+
+    // Helper function to serialize a single account to CSV using the given config.
+    fn to_csv(account: &Account, config: &OutputConfig) -> String {
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(true)
+            .from_writer(vec![]);
+        writer.serialize(account.output(config)).unwrap();
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_default_precision() {
+        let mut account = Account::new(1);
+        account.available_balance = 1.5;
+        account.held_balance = 2.25;
+
+        assert_eq!(
+            to_csv(&account, &OutputConfig::default()),
+            "client,available,held,total,locked\n1,1.5000,2.2500,3.7500,false\n"
+        );
+    }
+
+    #[test]
+    fn test_per_column_precision() {
+        let mut account = Account::new(1);
+        account.available_balance = 1.2345;
+        account.held_balance = 2.3456;
+
+        let config = OutputConfig {
+            held_precision: Some(2),
+            ..OutputConfig::default()
+        };
+
+        assert_eq!(
+            to_csv(&account, &config),
+            "client,available,held,total,locked\n1,1.2345,2.35,3.5801,false\n"
+        );
+    }
+
+    #[test]
+    fn test_display_scale_zero() {
+        let mut account = Account::new(1);
+        account.available_balance = 1000.0;
+
+        let config = OutputConfig {
+            display_scale: 0,
+            ..OutputConfig::default()
+        };
+
+        assert_eq!(
+            to_csv(&account, &config),
+            "client,available,held,total,locked\n1,1000,0,1000,false\n"
+        );
+    }
+
+    #[test]
+    fn test_display_scale_two() {
+        let mut account = Account::new(1);
+        account.available_balance = 12.345;
+        account.held_balance = 0.5;
+
+        // Per-column precision takes precedence over the display scale
+        let config = OutputConfig {
+            display_scale: 2,
+            total_precision: Some(4),
+            ..OutputConfig::default()
+        };
+
+        assert_eq!(
+            to_csv(&account, &config),
+            "client,available,held,total,locked\n1,12.35,0.50,12.8450,false\n"
+        );
+    }
+
+    #[test]
+    fn test_tiny_and_huge_balances() {
+        let mut account = Account::new(1);
+        account.available_balance = 0.00001;
+        account.held_balance = 1e30;
+
+        // Neither scientific notation nor comma separators may ever be emitted
+        let output = to_csv(&account, &OutputConfig::default());
+        assert_eq!(
+            output,
+            "client,available,held,total,locked\n\
+             1,0.0000,1000000015047466219876688855040.0000,1000000015047466219876688855040.0000,false\n"
+        );
+
+        let config = OutputConfig {
+            available_precision: Some(5),
+            ..OutputConfig::default()
+        };
+        assert!(to_csv(&account, &config).contains(",0.00001,"));
+    }
+
+    #[test]
+    fn test_total_equals_serialized_available_plus_held() {
+        // Parse a serialized balance into integer units of 0.0001
+        fn units(balance: &str) -> i64 {
+            balance.replace('.', "").parse().unwrap()
+        }
+
+        // Deterministic xorshift, so failures are reproducible
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        for _ in 0..1000 {
+            let mut account = Account::new(1);
+            for _ in 0..(next() % 20 + 1) {
+                let amount = (next() % 100_000_000) as f32 / 10_000.0;
+                account.available_balance += amount;
+
+                // Dispute some of the deposits
+                if next() % 3 == 0 {
+                    assert!(account.transition_held(amount));
+                }
+            }
+
+            let output = to_csv(&account, &OutputConfig::default());
+            let row: Vec<&str> = output.lines().nth(1).unwrap().split(',').collect();
+            assert_eq!(
+                units(row[3]),
+                units(row[1]) + units(row[2]),
+                "available {}, held {}, total {}",
+                row[1],
+                row[2],
+                row[3]
+            );
+        }
+    }
+
+    #[test]
+    fn test_locked_format() {
+        let mut account = Account::new(1);
+        account.locked = true;
+
+        let cases = [
+            (BoolFormat::TrueFalse, "true"),
+            (BoolFormat::YesNo, "Y"),
+            (BoolFormat::OneZero, "1"),
+        ];
+
+        for (locked_format, expected) in cases {
+            let config = OutputConfig {
+                locked_format,
+                ..OutputConfig::default()
+            };
+            assert_eq!(
+                to_csv(&account, &config),
+                format!(
+                    "client,available,held,total,locked\n1,0.0000,0.0000,0.0000,{}\n",
+                    expected
+                )
+            );
+        }
+
+        let config = OutputConfig {
+            locked_format: BoolFormat::YesNo,
+            ..OutputConfig::default()
+        };
+        assert_eq!(
+            to_csv(&Account::new(2), &config),
+            "client,available,held,total,locked\n2,0.0000,0.0000,0.0000,N\n"
+        );
+    }
+
+    #[test]
+    fn test_solvency() {
+        let mut account = Account::new(1);
+        account.available_balance = -5.0;
+        account.held_balance = 2.0;
+
+        let config = OutputConfig {
+            with_solvency: true,
+            ..OutputConfig::default()
+        };
+        assert!(!account.is_solvent());
+        assert_eq!(
+            to_csv(&account, &config),
+            "client,available,held,total,locked,solvent\n1,-5.0000,2.0000,-3.0000,false,false\n"
+        );
+
+        account.held_balance = 5.0;
+        assert!(account.is_solvent());
+    }
+
+    #[test]
+    fn test_json_amounts_are_strings() {
+        let mut account = Account::new(1);
+        account.available_balance = 25.5;
+
+        assert_eq!(
+            serde_json::to_string(&account.output(&OutputConfig::default())).unwrap(),
+            r#"{"client":1,"available":"25.5000","held":"0.0000","total":"25.5000","locked":"false"}"#
+        );
+    }
+
+    #[test]
+    fn test_transition_held() {
+        let mut account = Account::new(1);
+        account.available_balance = 100.0;
+
+        // Disputing keeps the total balance
+        assert!(account.transition_held(40.0));
+        assert_eq!(account.available_balance, 60.0);
+        assert_eq!(account.held_balance, 40.0);
+        assert_eq!(account.total_balance(), 100.0);
+
+        // Releasing keeps the total balance as well
+        assert!(account.transition_held(-40.0));
+        assert_eq!(account.available_balance, 100.0);
+        assert_eq!(account.held_balance, 0.0);
+
+        // Overflows leave the account untouched
+        account.held_balance = f32::MAX;
+        assert!(!account.transition_held(f32::MAX));
+        assert_eq!(account.available_balance, 100.0);
+        assert_eq!(account.held_balance, f32::MAX);
+    }
+
+    #[test]
+    fn test_record_transaction_evicts_oldest_undisputed() {
+        let mut account = Account::new(1);
+        for tx_id in 1..=3 {
+            account.record_transaction(
+                tx_id.into(),
+                TransactionDetails::new(TransactionType::Deposit, 1.0),
+            );
+            account.evict_ledger_entries(3);
+        }
+        account
+            .transactions
+            .get_mut(&TxId::from(1))
+            .unwrap()
+            .dispute_state = DisputeState::Disputed;
+
+        // The disputed tx 1 is kept, so tx 2 is evicted instead
+        account.record_transaction(
+            4.into(),
+            TransactionDetails::new(TransactionType::Deposit, 1.0),
+        );
+        let evicted = account.evict_ledger_entries(3);
+        assert_eq!(
+            evicted,
+            vec![(
+                1,
+                TxId::from(2),
+                TransactionDetails::new(TransactionType::Deposit, 1.0)
+            )]
+        );
+        let mut tx_ids: Vec<_> = account.transactions.keys().copied().collect();
+        tx_ids.sort_unstable();
+        assert_eq!(tx_ids, vec![TxId::from(1), TxId::from(3), TxId::from(4)]);
+    }
+
+    #[test]
+    fn test_remove_transaction_keeps_ledger_order() {
+        let mut account = Account::new(1);
+        for tx_id in 1..=3 {
+            account.record_transaction(
+                tx_id.into(),
+                TransactionDetails::new(TransactionType::Deposit, 1.0),
+            );
+            account.evict_ledger_entries(3);
+        }
+
+        assert!(account.remove_transaction(3.into()).is_some());
+        assert!(account.remove_transaction(3.into()).is_none());
+        for tx_id in 4..=5 {
+            account.record_transaction(
+                tx_id.into(),
+                TransactionDetails::new(TransactionType::Deposit, 1.0),
+            );
+            account.evict_ledger_entries(3);
+        }
+
+        // The removed id is gone from the order, so only tx 1 is evicted
+        assert_eq!(
+            account.ledger_order,
+            vec![TxId::from(2), TxId::from(4), TxId::from(5)]
+        );
+        assert_eq!(account.transactions.len(), 3);
+    }
+
+    #[test]
+    fn test_set_dispute_state() {
+        let mut account = Account::new(1);
+        for tx_id in 1..=2 {
+            account.record_transaction(
+                tx_id.into(),
+                TransactionDetails::new(TransactionType::Deposit, 1.0),
+            );
+        }
+
+        account.set_dispute_state(1.into(), DisputeState::Disputed);
+        account.set_dispute_state(2.into(), DisputeState::Disputed);
+        assert_eq!(account.open_disputes(), 2);
+
+        // Staying disputed doesn't count twice
+        account.set_dispute_state(2.into(), DisputeState::Disputed);
+        assert_eq!(account.open_disputes(), 2);
+
+        account.set_dispute_state(1.into(), DisputeState::Resolved);
+        account.set_dispute_state(2.into(), DisputeState::ChargedBack);
+        assert_eq!(account.open_disputes(), 0);
+
+        // Unknown transactions are ignored
+        assert!(account
+            .set_dispute_state(3.into(), DisputeState::Disputed)
+            .is_none());
+        assert_eq!(account.open_disputes(), 0);
+    }
+
+    #[test]
+    fn test_account_equality() {
+        let mut a = Account::new(1);
+        a.available_balance = 10.0;
+        a.transactions.insert(
+            1.into(),
+            TransactionDetails::new(TransactionType::Deposit, 10.0),
+        );
+
+        let mut b = Account::new(1);
+        b.available_balance = 10.0;
+        b.transactions.insert(
+            1.into(),
+            TransactionDetails::new(TransactionType::Deposit, 10.0),
+        );
+
+        assert_eq!(a, b);
+
+        b.transactions
+            .get_mut(&TxId::from(1))
+            .unwrap()
+            .dispute_state = DisputeState::Disputed;
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// Counters of the deposits and withdrawals applied to an account, e.g. for fraud analytics.
+///
+/// The counters only ever grow. Disputes, resolves, chargebacks and reversals don't affect them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct AccountActivity {
+    /// Number of applied deposits.
+    pub deposit_count: u64,
+    /// Number of applied withdrawals.
+    pub withdrawal_count: u64,
+    /// Sum of all applied deposit amounts.
+    pub total_deposited: f32,
+    /// Sum of all applied withdrawal amounts.
+    pub total_withdrawn: f32,
+}
@@ -0,0 +1,80 @@
+use super::Account;
+
+/// A C-compatible view of an account, for use across an FFI boundary.
+///
+/// Balances are in integer cents, rounded to the nearest cent.
+/// The total is always the exact sum of the available and held balance.
+/// Balances beyond the range of `i64` cents saturate at its bounds.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountC {
+    pub client_id: u16,
+    pub available_cents: i64,
+    pub held_cents: i64,
+    pub total_cents: i64,
+    /// `1` if the account is locked, `0` otherwise.
+    pub locked: u8,
+}
+
+impl From<&Account> for AccountC {
+    fn from(account: &Account) -> Self {
+        // Convert using f64, so the rounding isn't affected by f32 precision
+        let to_cents = |balance: f32| (f64::from(balance) * 100.0).round() as i64;
+        let available_cents = to_cents(account.available_balance);
+        let held_cents = to_cents(account.held_balance);
+        AccountC {
+            client_id: account.client_id,
+            available_cents,
+            held_cents,
+            total_cents: available_cents.saturating_add(held_cents),
+            locked: u8::from(account.locked),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AccountC;
+    use crate::engine::Account;
+
+    #[test]
+    fn test_cents_conversion() {
+        let cases = [
+            (0.0, 0.0, 0, 0),
+            (10.0, 2.5, 1000, 250),
+            (0.1, 0.2, 10, 20),
+            (1234.56, 0.01, 123456, 1),
+            (-5.05, 99.99, -505, 9999),
+        ];
+
+        for (available_balance, held_balance, available_cents, held_cents) in cases {
+            let mut account = Account::new(7);
+            account.available_balance = available_balance;
+            account.held_balance = held_balance;
+            account.locked = true;
+
+            assert_eq!(
+                AccountC::from(&account),
+                AccountC {
+                    client_id: 7,
+                    available_cents,
+                    held_cents,
+                    total_cents: available_cents + held_cents,
+                    locked: 1,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn test_cents_saturation() {
+        let mut account = Account::new(1);
+        account.available_balance = 1e30;
+        account.held_balance = 1e30;
+
+        let account = AccountC::from(&account);
+        assert_eq!(account.available_cents, i64::MAX);
+        assert_eq!(account.held_cents, i64::MAX);
+        assert_eq!(account.total_cents, i64::MAX);
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use super::{Account, AccountKey};
+
+/// Balances that differ by less than this are considered equal,
+/// which is below the precision of the default output.
+const TOLERANCE: f32 = 0.0001;
+
+/// A difference between two sets of accounts, as reported by `diff_accounts`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccountDiff {
+    /// The account only exists in the first set.
+    Removed(AccountKey),
+    /// The account only exists in the second set.
+    Added(AccountKey),
+    /// The balances or the lock state of the account differ.
+    /// Every pair contains the value of the first set, then the value of the second set.
+    Changed {
+        key: AccountKey,
+        available: (f32, f32),
+        held: (f32, f32),
+        locked: (bool, bool),
+    },
+}
+
+/// Compare two sets of accounts by account key, ignoring their order.
+///
+/// Balances are compared within a small tolerance, so accounts that only differ
+/// by float rounding are considered equal. The result is sorted by account key.
+pub fn diff_accounts(a: &[Account], b: &[Account]) -> Vec<AccountDiff> {
+    // Pair up accounts by key
+    let mut pairs: BTreeMap<AccountKey, (Option<&Account>, Option<&Account>)> = BTreeMap::new();
+    for account in a {
+        pairs.entry(account.key()).or_default().0 = Some(account);
+    }
+    for account in b {
+        pairs.entry(account.key()).or_default().1 = Some(account);
+    }
+
+    pairs
+        .into_iter()
+        .filter_map(|(key, pair)| match pair {
+            (Some(a), Some(b)) => {
+                let differs = (a.available_balance - b.available_balance).abs() >= TOLERANCE
+                    || (a.held_balance - b.held_balance).abs() >= TOLERANCE
+                    || a.locked != b.locked;
+                differs.then_some(AccountDiff::Changed {
+                    key,
+                    available: (a.available_balance, b.available_balance),
+                    held: (a.held_balance, b.held_balance),
+                    locked: (a.locked, b.locked),
+                })
+            }
+            (Some(_), None) => Some(AccountDiff::Removed(key)),
+            (None, Some(_)) => Some(AccountDiff::Added(key)),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{diff_accounts, AccountDiff};
+    use crate::engine::{Account, AccountKey};
+
+    // Helper function to build an account with the given balances.
+    fn account(client_id: u16, available_balance: f32, held_balance: f32) -> Account {
+        let mut account = Account::new(client_id);
+        account.available_balance = available_balance;
+        account.held_balance = held_balance;
+        account
+    }
+
+    #[test]
+    fn test_diff_accounts() {
+        let a = vec![
+            account(1, 10.0, 0.0),
+            account(2, 5.0, 2.5),
+            account(3, 1.0, 0.0),
+        ];
+        let b = vec![
+            account(4, 1.0, 0.0),
+            account(2, 5.0, 0.0),
+            account(1, 10.00001, 0.0),
+        ];
+
+        assert_eq!(
+            diff_accounts(&a, &b),
+            vec![
+                AccountDiff::Changed {
+                    key: AccountKey::new(None, 2),
+                    available: (5.0, 5.0),
+                    held: (2.5, 0.0),
+                    locked: (false, false),
+                },
+                AccountDiff::Removed(AccountKey::new(None, 3)),
+                AccountDiff::Added(AccountKey::new(None, 4)),
+            ]
+        );
+        assert!(diff_accounts(&a, &a).is_empty());
+    }
+}
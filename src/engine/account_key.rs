@@ -0,0 +1,15 @@
+/// Key identifying an account.
+///
+/// Accounts of different tenants are fully isolated, even if they share a client id.
+/// Records without a tenant belong to the implicit default tenant `None`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AccountKey {
+    pub tenant: Option<String>,
+    pub client_id: u16,
+}
+
+impl AccountKey {
+    pub fn new(tenant: Option<String>, client_id: u16) -> Self {
+        AccountKey { tenant, client_id }
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, RwLock},
+};
+
+use super::{Account, AccountKey};
+
+/// Shared, lockable handle to a single account.
+pub type AccountHandle = Arc<Mutex<Account>>;
+
+/// Storage backend for the accounts of a `TransactionEngine`.
+///
+/// The engine only ever accesses accounts through this trait, which allows
+/// swapping the storage without touching the transaction processing logic.
+pub trait AccountStore: Send + Sync {
+    /// Return the account for the given key, if it exists.
+    fn get(&self, key: &AccountKey) -> Result<Option<AccountHandle>>;
+
+    /// Return the account for the given key, creating it if it doesn't exist.
+    ///
+    /// Implementations must guarantee that concurrent callers for the same
+    /// new account always end up with the same account.
+    fn get_or_create(&self, key: &AccountKey) -> Result<AccountHandle>;
+
+    /// Remove the account for the given key. Does nothing if it doesn't exist.
+    fn remove(&self, key: &AccountKey) -> Result<()>;
+
+    /// Return an iterator over all accounts.
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = AccountHandle> + '_>>;
+
+    /// Return the number of accounts.
+    ///
+    /// The default implementation counts the accounts returned by `iter`.
+    fn len(&self) -> Result<usize> {
+        Ok(self.iter()?.count())
+    }
+
+    /// Whether the store contains no accounts.
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+}
+
+/// The default `AccountStore`, keeping all accounts in memory.
+///
+/// Accounts are indexed by their `AccountKey`, which allows finding accounts
+/// without having to acquire locks for all accounts.
+#[derive(Default)]
+pub struct InMemoryAccountStore {
+    accounts: RwLock<HashMap<AccountKey, AccountHandle>>,
+}
+
+impl InMemoryAccountStore {
+    /// Construct a new store with room for the given number of accounts,
+    /// avoiding reallocations of the index while accounts are created.
+    pub fn with_capacity(expected_clients: usize) -> Self {
+        InMemoryAccountStore {
+            accounts: RwLock::new(HashMap::with_capacity(expected_clients)),
+        }
+    }
+}
+
+impl AccountStore for InMemoryAccountStore {
+    fn get(&self, key: &AccountKey) -> Result<Option<AccountHandle>> {
+        let accounts = self
+            .accounts
+            .read()
+            .map_err(|_| anyhow!("Unable to acquire read-lock on accounts."))?;
+        Ok(accounts.get(key).map(Arc::clone))
+    }
+
+    fn get_or_create(&self, key: &AccountKey) -> Result<AccountHandle> {
+        // Fast path: The account already exists
+        if let Some(account) = self.get(key)? {
+            return Ok(account);
+        }
+
+        // Slow path: Briefly acquire the write-lock and create the account.
+        // Creation uses an entry-style get-or-insert under the write-lock,
+        // so concurrent callers for the same new client always share one account.
+        let mut accounts = self
+            .accounts
+            .write()
+            .map_err(|_| anyhow!("Unable to acquire write-lock on accounts."))?;
+        let account = accounts
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(Account::from_key(key.clone()))));
+        Ok(Arc::clone(account))
+    }
+
+    fn remove(&self, key: &AccountKey) -> Result<()> {
+        let mut accounts = self
+            .accounts
+            .write()
+            .map_err(|_| anyhow!("Unable to acquire write-lock on accounts."))?;
+        accounts.remove(key);
+        Ok(())
+    }
+
+    fn iter(&self) -> Result<Box<dyn Iterator<Item = AccountHandle> + '_>> {
+        let accounts = self
+            .accounts
+            .read()
+            .map_err(|_| anyhow!("Unable to acquire read-lock on accounts."))?;
+        let handles: Vec<AccountHandle> = accounts.values().map(Arc::clone).collect();
+        Ok(Box::new(handles.into_iter()))
+    }
+
+    fn len(&self) -> Result<usize> {
+        let accounts = self
+            .accounts
+            .read()
+            .map_err(|_| anyhow!("Unable to acquire read-lock on accounts."))?;
+        Ok(accounts.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountStore, InMemoryAccountStore};
+    use crate::engine::AccountKey;
+
+    #[test]
+    fn test_with_capacity() {
+        let store = InMemoryAccountStore::with_capacity(1000);
+        let capacity = store.accounts.read().unwrap().capacity();
+        assert!(capacity >= 1000);
+
+        // Creating the expected number of accounts never grows the index
+        for client_id in 0..1000 {
+            store
+                .get_or_create(&AccountKey::new(None, client_id))
+                .unwrap();
+        }
+        let accounts = store.accounts.read().unwrap();
+        assert_eq!(accounts.len(), 1000);
+        assert_eq!(accounts.capacity(), capacity);
+    }
+}
@@ -0,0 +1,17 @@
+/// Balances summed up over all accounts.
+///
+/// The sums are accumulated in `f64`, so they don't drift with the number of accounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AggregateBalances {
+    /// Sum of the available balances of all accounts.
+    pub total_available: f64,
+    /// Sum of the held balances of all accounts.
+    pub total_held: f64,
+}
+
+impl AggregateBalances {
+    /// Return the sum of all available and held balances.
+    pub fn total(&self) -> f64 {
+        self.total_available + self.total_held
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Unit of the transaction amounts in the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AmountUnit {
+    /// Decimal amounts in the `amount` column, e.g. `12.50`.
+    #[default]
+    Decimal,
+    /// Integer cents in the `amount_cents` column, e.g. `1250`.
+    Cents,
+}
+
+impl FromStr for AmountUnit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "decimal" => Ok(AmountUnit::Decimal),
+            "cents" => Ok(AmountUnit::Cents),
+            _ => Err(anyhow!(
+                "Unknown amount unit '{}', expected one of: decimal, cents.",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Format used to render boolean output columns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BoolFormat {
+    /// `true` / `false`
+    #[default]
+    TrueFalse,
+    /// `Y` / `N`
+    YesNo,
+    /// `1` / `0`
+    OneZero,
+}
+
+impl BoolFormat {
+    /// Render the given value using this format.
+    pub fn format(&self, value: bool) -> &'static str {
+        match (self, value) {
+            (BoolFormat::TrueFalse, true) => "true",
+            (BoolFormat::TrueFalse, false) => "false",
+            (BoolFormat::YesNo, true) => "Y",
+            (BoolFormat::YesNo, false) => "N",
+            (BoolFormat::OneZero, true) => "1",
+            (BoolFormat::OneZero, false) => "0",
+        }
+    }
+}
+
+impl FromStr for BoolFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "true-false" => Ok(BoolFormat::TrueFalse),
+            "yes-no" => Ok(BoolFormat::YesNo),
+            "one-zero" => Ok(BoolFormat::OneZero),
+            _ => Err(anyhow!(
+                "Unknown bool format '{}', expected one of: true-false, yes-no, one-zero.",
+                s
+            )),
+        }
+    }
+}
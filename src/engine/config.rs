@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::Path;
+
+use super::{EngineConfig, OutputConfig};
+
+/// Combined engine and output settings, as read from a TOML config file.
+///
+/// Example:
+/// ```toml
+/// [engine]
+/// auto_lock_on_negative = true
+///
+/// [output]
+/// held_precision = 2
+/// locked_format = "yes-no"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub engine: EngineConfig,
+    pub output: OutputConfig,
+}
+
+impl Config {
+    /// Parse the config from a TOML string.
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).context("Unable to parse config file.")
+    }
+
+    /// Read and parse the config from a TOML file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let toml = std::fs::read_to_string(path).context("Unable to read config file.")?;
+        Self::from_toml(&toml)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::engine::BoolFormat;
+
+    #[test]
+    fn test_load_config_file() {
+        let path =
+            std::env::temp_dir().join(format!("tx-engine-config-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"
+                [engine]
+                auto_lock_on_negative = true
+
+                [output]
+                held_precision = 2
+                locked_format = "yes-no"
+            "#,
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(config.engine.auto_lock_on_negative);
+        assert_eq!(config.output.display_scale, 4);
+        assert_eq!(config.output.available_precision, None);
+        assert_eq!(config.output.held_precision, Some(2));
+        assert_eq!(config.output.total_precision, None);
+        assert_eq!(config.output.locked_format, BoolFormat::YesNo);
+    }
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = Config::from_toml("").unwrap();
+
+        assert!(!config.engine.auto_lock_on_negative);
+        assert_eq!(config.output.display_scale, 4);
+        assert_eq!(config.output.held_precision, None);
+        assert_eq!(config.output.locked_format, BoolFormat::TrueFalse);
+    }
+
+    #[test]
+    fn test_unknown_config_key() {
+        assert!(Config::from_toml("[output]\nunknown_key = 1\n").is_err());
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::{anyhow, Error};
+use csv::StringRecord;
+use std::str::FromStr;
+
+use super::TransactionRecord;
+
+/// Decimal separator of the amounts in the input.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum DecimalSeparator {
+    /// Amounts like `50.25`.
+    #[default]
+    Dot,
+    /// Amounts like `50,25`, as used in many European countries.
+    /// This requires a field delimiter other than a comma, unless amounts are quoted.
+    Comma,
+}
+
+impl DecimalSeparator {
+    /// Deserialize a CSV record, reading the `amount` column with this decimal separator.
+    pub fn deserialize_record(
+        &self,
+        record: &StringRecord,
+        headers: &StringRecord,
+    ) -> csv::Result<TransactionRecord> {
+        if *self == DecimalSeparator::Dot {
+            return record.deserialize(Some(headers));
+        }
+
+        // Convert the decimal comma of the amount to a dot
+        let mut converted: StringRecord = record
+            .iter()
+            .enumerate()
+            .map(|(index, field)| match headers.get(index) {
+                Some("amount") => field.replace(',', "."),
+                _ => field.to_string(),
+            })
+            .collect();
+        converted.set_position(record.position().cloned());
+        converted.deserialize(Some(headers))
+    }
+}
+
+impl FromStr for DecimalSeparator {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(DecimalSeparator::Dot),
+            "comma" => Ok(DecimalSeparator::Comma),
+            _ => Err(anyhow!(
+                "Unknown decimal separator '{}', expected one of: dot, comma.",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use super::TransactionType;
+
+/// Dispute state of a recorded transaction.
+///
+/// Valid transitions:
+/// - `None` | `Resolved` => `Disputed` (dispute)
+/// - `Disputed` => `Resolved` (resolve)
+/// - `Disputed` => `ChargedBack` (chargeback)
+/// - `None` | `Resolved` => `Reversed` (reversal)
+///
+/// `ChargedBack` and `Reversed` are final.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DisputeState {
+    /// The transaction has never been disputed.
+    None,
+    /// The transaction is currently disputed and its amount is held.
+    Disputed,
+    /// The dispute has been resolved and the held amount released.
+    Resolved,
+    /// The transaction has been charged back.
+    ChargedBack,
+    /// The transaction has been reversed.
+    Reversed,
+}
+
+impl DisputeState {
+    /// Return the state after applying a transaction of the given type,
+    /// or `None` if the transition is invalid.
+    pub fn transition(self, tx_type: &TransactionType) -> Option<DisputeState> {
+        match (self, tx_type) {
+            (DisputeState::None | DisputeState::Resolved, TransactionType::Dispute) => {
+                Some(DisputeState::Disputed)
+            }
+            (DisputeState::Disputed, TransactionType::Resolve) => Some(DisputeState::Resolved),
+            (DisputeState::Disputed, TransactionType::Chargeback) => {
+                Some(DisputeState::ChargedBack)
+            }
+            (DisputeState::None | DisputeState::Resolved, TransactionType::Reversal) => {
+                Some(DisputeState::Reversed)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisputeState, TransactionType};
+
+    #[test]
+    fn test_transitions() {
+        use DisputeState::*;
+        use TransactionType::*;
+
+        let cases = [
+            (None, Dispute, Some(Disputed)),
+            (None, Resolve, Option::None),
+            (None, Chargeback, Option::None),
+            (Disputed, Dispute, Option::None),
+            (Disputed, Resolve, Some(Resolved)),
+            (Disputed, Chargeback, Some(ChargedBack)),
+            (Resolved, Dispute, Some(Disputed)),
+            (Resolved, Resolve, Option::None),
+            (Resolved, Chargeback, Option::None),
+            (ChargedBack, Dispute, Option::None),
+            (ChargedBack, Resolve, Option::None),
+            (ChargedBack, Chargeback, Option::None),
+            (None, Reversal, Some(Reversed)),
+            (Disputed, Reversal, Option::None),
+            (Resolved, Reversal, Some(Reversed)),
+            (ChargedBack, Reversal, Option::None),
+            (Reversed, Dispute, Option::None),
+            (Reversed, Resolve, Option::None),
+            (Reversed, Chargeback, Option::None),
+            (Reversed, Reversal, Option::None),
+        ];
+
+        for (state, tx_type, expected) in cases {
+            assert_eq!(
+                state.transition(&tx_type),
+                expected,
+                "{:?} + {:?}",
+                state,
+                tx_type
+            );
+        }
+
+        for state in [None, Disputed, Resolved, ChargedBack, Reversed] {
+            assert_eq!(state.transition(&Deposit), Option::None);
+            assert_eq!(state.transition(&Withdraw), Option::None);
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Deserialize;
+use std::collections::HashSet;
+
+use super::{
+    AmountUnit, PartialChargebackPolicy, TransactionStatus, WithdrawalDisputePolicy,
+    WithdrawalOrder, ZeroSumCheck,
+};
+
+/// Settings controlling the behavior of the `TransactionEngine`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EngineConfig {
+    /// Lock accounts as soon as their available balance becomes negative,
+    /// e.g. when a dispute references already spent funds.
+    pub auto_lock_on_negative: bool,
+
+    /// Require the first record of every client to be a deposit.
+    /// Other records for unknown clients are ignored instead of creating an empty account.
+    pub require_opening_deposit: bool,
+
+    /// Interpret disputes with tx id 0 or without a tx id as referencing the client's most recent
+    /// deposit that can still be disputed. This deviates from the specification.
+    pub dispute_latest_deposit: bool,
+
+    /// Available balance every account must retain.
+    /// Withdrawals that would leave less than this are rejected.
+    pub minimum_balance: f32,
+
+    /// Keep processing disputes, resolves and chargebacks on locked accounts,
+    /// e.g. to register disputes for later manual review.
+    /// Deposits and withdrawals are always ignored on locked accounts.
+    pub process_disputes_on_locked: bool,
+
+    /// Keep processing resolves on locked accounts, releasing held funds
+    /// while the account stays locked. All other records are still ignored.
+    pub resolve_on_locked: bool,
+
+    /// Apply all records regardless of the lock state, e.g. when replaying a historical ledger
+    /// in which accounts were locked by the replayed records themselves.
+    /// This is dangerous for live data, since locked accounts keep accepting deposits and withdrawals.
+    pub replay_mode: bool,
+
+    /// Apply reversals even if they leave a negative available balance.
+    /// Such reversals are rejected otherwise.
+    pub allow_overdrawing_reversals: bool,
+
+    /// Skip records that can't be deserialized instead of aborting,
+    /// recording a warning with their position. This applies to all processing methods.
+    pub skip_unparsable_records: bool,
+
+    /// Maximum number of open disputes per account.
+    /// Further disputes are ignored until an open dispute is resolved or charged back.
+    pub max_open_disputes: Option<usize>,
+
+    /// How disputes referencing a withdrawal are handled.
+    pub withdrawal_dispute_policy: WithdrawalDisputePolicy,
+
+    /// Order in which withdrawals draw from the balances of the clients in `withdrawal_order_clients`.
+    pub withdrawal_order: WithdrawalOrder,
+
+    /// Clients the `withdrawal_order` applies to. All other clients draw from the available balance only.
+    /// The withdrawal order applies to all clients if this isn't set.
+    pub withdrawal_order_clients: Option<HashSet<u16>>,
+
+    /// How disputes referencing a partially charged back transaction are handled.
+    pub partial_chargeback_policy: PartialChargebackPolicy,
+
+    /// Number of decimal places all amounts are rounded to before they're applied,
+    /// so the ledger and the balances are consistent at that precision.
+    /// Amounts are kept at full precision if this isn't set.
+    pub ingest_rounding: Option<usize>,
+
+    /// Maximum number of decimal places allowed for deposit amounts.
+    /// Deposits with more decimal places are rejected.
+    pub deposit_precision: Option<usize>,
+
+    /// Maximum number of decimal places allowed for withdrawal amounts.
+    /// Withdrawals with more decimal places are rejected.
+    pub withdrawal_precision: Option<usize>,
+
+    /// Maximum amount of a single deposit or withdrawal.
+    /// Transactions exceeding it are rejected.
+    pub max_transaction_amount: Option<f32>,
+
+    /// Total balance no real account ever exceeds.
+    /// A warning is recorded whenever a transaction makes an account's total balance cross it.
+    pub total_balance_ceiling: Option<f32>,
+
+    /// Expected aggregate total balance of all accounts, checked by `TransactionEngine::check_zero_sum`.
+    /// A warning is recorded if the aggregate isn't within tolerance. Nothing is checked if this isn't set.
+    pub assert_zero_sum: Option<ZeroSumCheck>,
+
+    /// Client ids that are reserved, e.g. as sentinels, and must never appear in the input.
+    /// Records of these clients are rejected as invalid.
+    pub reserved_client_ids: HashSet<u16>,
+
+    /// Clients whose records are processed. Records of all other clients are ignored.
+    /// All clients are allowed if this isn't set.
+    pub allowed_clients: Option<HashSet<u16>>,
+
+    /// Sources whose records are processed. Records of all other sources,
+    /// including records without a source, are ignored.
+    /// All sources are allowed if this isn't set.
+    pub allowed_sources: Option<HashSet<String>>,
+
+    /// Maximum number of transactions kept in each account's ledger.
+    /// The oldest undisputed transactions are evicted first and can't be disputed anymore.
+    /// All transactions are kept if this isn't set.
+    pub max_ledger_entries: Option<usize>,
+
+    /// Number of processed records after which the engine switches to streaming, as long as
+    /// no dispute, resolve, chargeback or reversal has been seen yet. Streaming drops all ledgers
+    /// and stops recording transactions, so such records are an error afterwards.
+    /// The engine never switches if this isn't set.
+    pub auto_stream_threshold: Option<usize>,
+
+    /// Statuses of records that are applied. Records with other statuses are ignored.
+    /// Only `posted` records are applied if this isn't set. Records without a status are always applied.
+    pub applied_statuses: Option<HashSet<TransactionStatus>>,
+
+    /// Number of ledger entries pre-allocated for every new account.
+    /// This is only a performance hint and doesn't limit the ledger.
+    pub ledger_capacity: usize,
+
+    /// Number of most recently applied records that can be rolled back.
+    /// Journaling is disabled if this is zero.
+    pub journal_size: usize,
+
+    /// Unit of the transaction amounts in the input.
+    pub amount_unit: AmountUnit,
+}
@@ -0,0 +1,43 @@
+use std::{error::Error, fmt};
+
+use super::TxId;
+
+/// A typed error returned by the `TransactionEngine`.
+///
+/// Engine methods return `anyhow::Result`, use `downcast_ref::<EngineError>()`
+/// to inspect the specific error.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineError {
+    /// A balance mutation would overflow.
+    Overflow { client_id: u16, tx_id: TxId },
+    /// The deadline passed after processing the given number of records.
+    /// Accounts stay valid and contain all records processed up to that point.
+    Timeout { processed: usize },
+    /// A dispute-family record appeared after the engine switched to streaming,
+    /// so the referenced transaction is no longer in the ledger.
+    DisputeAfterStreaming { client_id: u16, tx_id: TxId },
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Overflow { client_id, tx_id } => write!(
+                f,
+                "Balance overflow for client {} while processing tx {}.",
+                client_id, tx_id
+            ),
+            EngineError::Timeout { processed } => write!(
+                f,
+                "Deadline exceeded after processing {} records.",
+                processed
+            ),
+            EngineError::DisputeAfterStreaming { client_id, tx_id } => write!(
+                f,
+                "Unable to process tx {} of client {}: Disputes aren't supported after switching to streaming.",
+                tx_id, client_id
+            ),
+        }
+    }
+}
+
+impl Error for EngineError {}
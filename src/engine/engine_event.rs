@@ -0,0 +1,46 @@
+use super::TxId;
+
+/// A domain event emitted by the engine whenever a record changed an account.
+///
+/// Records that are ignored or rejected don't emit any events.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+    /// Funds were deposited to the available balance.
+    Deposited {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// Funds were withdrawn from the available balance.
+    Withdrawn {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// A transaction with the given amount was disputed.
+    DisputeOpened {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// The given amount of a dispute was undone, which may be only part of it.
+    Resolved {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// The given held amount of a disputed transaction was charged back.
+    ChargedBack {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// A deposit with the given amount was reversed.
+    Reversed {
+        client_id: u16,
+        tx_id: TxId,
+        amount: f32,
+    },
+    /// The account was locked by the given transaction.
+    AccountLocked { client_id: u16, tx_id: TxId },
+}
@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::TransactionType;
+
+/// Counters describing the work done by the `TransactionEngine`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EngineStats {
+    /// Number of processed records, including rejected ones.
+    pub processed: u64,
+
+    /// Number of records that were ignored without changing any account,
+    /// e.g. withdrawals with insufficient funds or disputes of unknown transactions.
+    pub rejected: u64,
+
+    /// Number of applied records per transaction type. Rejected records aren't counted.
+    pub by_type: HashMap<TransactionType, u64>,
+
+    /// Number of processed records per source, including rejected ones.
+    /// Records without a source aren't counted.
+    pub by_source: HashMap<String, u64>,
+}
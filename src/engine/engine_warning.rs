@@ -0,0 +1,52 @@
+use std::fmt;
+
+use super::TxId;
+
+/// A non-fatal anomaly encountered while processing transactions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineWarning {
+    /// A resolve referenced a transaction that has already been charged back.
+    ResolveAfterChargeback { client_id: u16, tx_id: TxId },
+    /// A transaction made the total balance of an account exceed the configured ceiling.
+    TotalBalanceCeilingExceeded {
+        client_id: u16,
+        tx_id: TxId,
+        total: f32,
+    },
+    /// A record couldn't be deserialized and was skipped.
+    ///
+    /// The position is the line number for CSV input read by the CLI,
+    /// or the 1-based index of the record otherwise.
+    UnparsableRecord { position: u64, message: String },
+    /// The aggregate total balance of all accounts isn't within tolerance of the expected value.
+    ZeroSumViolated { expected: f64, actual: f64 },
+}
+
+impl fmt::Display for EngineWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineWarning::ResolveAfterChargeback { client_id, tx_id } => write!(
+                f,
+                "Ignoring resolve for client {}, tx {}: Transaction was already charged back.",
+                client_id, tx_id
+            ),
+            EngineWarning::TotalBalanceCeilingExceeded {
+                client_id,
+                tx_id,
+                total,
+            } => write!(
+                f,
+                "Total balance of client {} exceeds the ceiling after tx {}: {:.4}.",
+                client_id, tx_id, total
+            ),
+            EngineWarning::UnparsableRecord { position, message } => {
+                write!(f, "Skipping unparsable record {}: {}", position, message)
+            }
+            EngineWarning::ZeroSumViolated { expected, actual } => write!(
+                f,
+                "Aggregate total balance is {:.4}, but {:.4} was expected.",
+                actual, expected
+            ),
+        }
+    }
+}
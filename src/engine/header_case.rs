@@ -0,0 +1,49 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Letter case used for the output header row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HeaderCase {
+    /// `client`
+    #[default]
+    Lower,
+    /// `CLIENT`
+    Upper,
+    /// `Client`
+    Title,
+}
+
+impl HeaderCase {
+    /// Render the given header name using this case.
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            HeaderCase::Lower => name.to_lowercase(),
+            HeaderCase::Upper => name.to_uppercase(),
+            HeaderCase::Title => {
+                let mut chars = name.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for HeaderCase {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lower" => Ok(HeaderCase::Lower),
+            "upper" => Ok(HeaderCase::Upper),
+            "title" => Ok(HeaderCase::Title),
+            _ => Err(anyhow!(
+                "Unknown header case '{}', expected one of: lower, upper, title.",
+                s
+            )),
+        }
+    }
+}
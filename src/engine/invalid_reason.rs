@@ -0,0 +1,26 @@
+use std::fmt;
+
+/// The reason a record is invalid, see `TransactionRecord::validate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidReason {
+    /// A deposit or withdrawal has no amount.
+    MissingAmount,
+    /// A dispute, chargeback or reversal carries an amount.
+    UnexpectedAmount,
+    /// The amount is zero or negative.
+    NonPositiveAmount,
+    /// The amount is NaN or infinite.
+    NonFiniteAmount,
+}
+
+impl fmt::Display for InvalidReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            InvalidReason::MissingAmount => "The amount is missing.",
+            InvalidReason::UnexpectedAmount => "The transaction type doesn't take an amount.",
+            InvalidReason::NonPositiveAmount => "The amount must be positive.",
+            InvalidReason::NonFiniteAmount => "The amount must be a finite number.",
+        };
+        write!(f, "{}", message)
+    }
+}
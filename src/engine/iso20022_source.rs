@@ -0,0 +1,183 @@
+use anyhow::{anyhow, Context, Result};
+use roxmltree::{Document, Node};
+use std::convert::Infallible;
+
+use super::{TransactionRecord, TransactionType, TxId};
+
+/// Source of transaction records read from an ISO 20022 `pain.001` (customer credit transfer initiation) document.
+///
+/// Every `PmtInf` (payment information) block belongs to a single client,
+/// and every `CdtTrfTxInf` (credit transfer transaction information) inside it is mapped to one record:
+///
+/// | Record field | Element                              |
+/// |--------------|--------------------------------------|
+/// | `client`     | `PmtInf/DbtrAcct/Id/Othr/Id`         |
+/// | `tx`         | `CdtTrfTxInf/PmtId/EndToEndId`       |
+/// | `amount`     | `CdtTrfTxInf/Amt/InstdAmt`           |
+/// | `currency`   | `CdtTrfTxInf/Amt/InstdAmt/@Ccy`      |
+///
+/// Every credit transfer debits the client's account, so all records are withdrawals.
+///
+/// Client and transaction ids must be numeric.
+pub struct Iso20022Source {
+    records: Vec<TransactionRecord>,
+}
+
+impl Iso20022Source {
+    /// Parse all transaction records from the given `pain.001` document.
+    pub fn parse(xml: &str) -> Result<Self> {
+        let document = Document::parse(xml).context("Unable to parse ISO 20022 document.")?;
+
+        let mut records = Vec::new();
+        for payment in document
+            .descendants()
+            .filter(|node| node.has_tag_name("PmtInf"))
+        {
+            // Find the client owning the debtor account
+            let client_id = child_text(payment, &["DbtrAcct", "Id", "Othr", "Id"])
+                .context("Payment information is missing the debtor account id.")?
+                .parse::<u16>()
+                .map_err(|_| anyhow!("Debtor account id is not a valid client id."))?;
+
+            for transfer in payment
+                .children()
+                .filter(|node| node.has_tag_name("CdtTrfTxInf"))
+            {
+                records.push(parse_transfer(transfer, client_id)?);
+            }
+        }
+
+        Ok(Iso20022Source { records })
+    }
+
+    /// Return all parsed records, ready to be passed to `TransactionEngine::process_records`.
+    pub fn records(
+        self,
+    ) -> impl Iterator<Item = std::result::Result<TransactionRecord, Infallible>> + Send + Sync
+    {
+        self.records.into_iter().map(Ok)
+    }
+}
+
+/// Map a single `CdtTrfTxInf` element to a transaction record.
+fn parse_transfer(transfer: Node, client_id: u16) -> Result<TransactionRecord> {
+    let transaction_id = child_text(transfer, &["PmtId", "EndToEndId"])
+        .context("Credit transfer is missing the end to end id.")?
+        .parse::<TxId>()
+        .map_err(|_| anyhow!("End to end id is not a valid transaction id."))?;
+    let amount_node = child_element(transfer, &["Amt", "InstdAmt"])
+        .context("Credit transfer is missing the instructed amount.")?;
+    let amount = amount_node
+        .text()
+        .map(str::trim)
+        .unwrap_or_default()
+        .parse::<f32>()
+        .map_err(|_| anyhow!("Instructed amount is not a valid amount."))?;
+
+    Ok(TransactionRecord {
+        r#type: TransactionType::Withdraw,
+        client_id,
+        transaction_id,
+        amount: Some(amount),
+        amount_places: None,
+        amount_cents: None,
+        tenant: None,
+        client_name: None,
+        currency: amount_node.attribute("Ccy").map(String::from),
+        batch: None,
+        status: None,
+        source: None,
+    })
+}
+
+/// Return the element at the given path of child element names.
+fn child_element<'a, 'input>(node: Node<'a, 'input>, path: &[&str]) -> Option<Node<'a, 'input>> {
+    let mut node = node;
+    for name in path {
+        node = node.children().find(|child| child.has_tag_name(*name))?;
+    }
+    Some(node)
+}
+
+/// Return the trimmed text of the element at the given path of child element names.
+fn child_text<'a>(node: Node<'a, '_>, path: &[&str]) -> Option<&'a str> {
+    child_element(node, path)?.text().map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Iso20022Source;
+    use crate::engine::TransactionType;
+    use crate::TransactionEngine;
+
+    const PAIN_001: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <Document xmlns="urn:iso:std:iso:20022:tech:xsd:pain.001.001.09">
+            <CstmrCdtTrfInitn>
+                <GrpHdr>
+                    <MsgId>MSG-1</MsgId>
+                    <NbOfTxs>3</NbOfTxs>
+                </GrpHdr>
+                <PmtInf>
+                    <PmtInfId>PMT-1</PmtInfId>
+                    <DbtrAcct><Id><Othr><Id>1</Id></Othr></Id></DbtrAcct>
+                    <CdtTrfTxInf>
+                        <PmtId><EndToEndId>1</EndToEndId></PmtId>
+                        <Amt><InstdAmt Ccy="EUR">100.00</InstdAmt></Amt>
+                    </CdtTrfTxInf>
+                    <CdtTrfTxInf>
+                        <PmtId><EndToEndId>2</EndToEndId></PmtId>
+                        <Amt><InstdAmt Ccy="EUR">25.50</InstdAmt></Amt>
+                    </CdtTrfTxInf>
+                </PmtInf>
+                <PmtInf>
+                    <PmtInfId>PMT-2</PmtInfId>
+                    <DbtrAcct><Id><Othr><Id>2</Id></Othr></Id></DbtrAcct>
+                    <CdtTrfTxInf>
+                        <PmtId><EndToEndId>3</EndToEndId></PmtId>
+                        <Amt><InstdAmt Ccy="EUR">10.00</InstdAmt></Amt>
+                    </CdtTrfTxInf>
+                </PmtInf>
+            </CstmrCdtTrfInitn>
+        </Document>"#;
+
+    #[tokio::test]
+    async fn test_process_pain_001() {
+        let source = Iso20022Source::parse(PAIN_001).unwrap();
+        let engine = TransactionEngine::new();
+        engine
+            .process_csv_bytes(
+                b"type,client,tx,amount,currency\ndeposit,1,10,200.0,EUR\ndeposit,2,20,50.0,EUR\n",
+            )
+            .await
+            .unwrap();
+        engine.process_records(source.records()).await.unwrap();
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].available_balance, 74.5);
+        assert_eq!(accounts[1].available_balance, 40.0);
+        assert_eq!(accounts[0].currency.as_deref(), Some("EUR"));
+    }
+
+    #[test]
+    fn test_transfer_mapping() {
+        let records: Vec<_> = Iso20022Source::parse(PAIN_001)
+            .unwrap()
+            .records()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records.len(), 3);
+        assert!(records
+            .iter()
+            .all(|record| record.r#type == TransactionType::Withdraw));
+        assert!(records
+            .iter()
+            .all(|record| record.currency.as_deref() == Some("EUR")));
+    }
+
+    #[test]
+    fn test_invalid_client_id() {
+        let xml = PAIN_001.replace("<Id>1</Id>", "<Id>DE89370400440532013000</Id>");
+        assert!(Iso20022Source::parse(&xml).is_err());
+    }
+}
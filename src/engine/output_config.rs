@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+use super::{BoolFormat, HeaderCase, OutputFormat};
+
+/// Settings controlling how accounts are written to the output.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputConfig {
+    /// Format of the output.
+    pub format: OutputFormat,
+    /// Number of decimal places for all balance columns, e.g. 2 for USD or 0 for JPY.
+    ///
+    /// This only affects the output, balances are kept at full precision internally.
+    pub display_scale: usize,
+    /// Number of decimal places for the `available` column, overriding `display_scale`.
+    pub available_precision: Option<usize>,
+    /// Number of decimal places for the `held` column, overriding `display_scale`.
+    pub held_precision: Option<usize>,
+    /// Number of decimal places for the `total` column, overriding `display_scale`.
+    pub total_precision: Option<usize>,
+    /// Format of the `locked` column and all other boolean columns.
+    pub locked_format: BoolFormat,
+    /// Letter case of the header row.
+    pub header_case: HeaderCase,
+    /// Emit a `solvent` column, which is false if the total balance is negative.
+    pub with_solvency: bool,
+    /// Emit the `client_name` and `currency` columns.
+    pub with_metadata: bool,
+    /// Emit the `deposit_count`, `withdrawal_count`, `total_deposited` and `total_withdrawn` columns.
+    pub with_activity: bool,
+}
+
+impl OutputConfig {
+    /// Return the header row of the output.
+    pub fn headers(&self) -> Vec<String> {
+        let mut headers = vec!["client", "available", "held", "total", "locked"];
+        if self.with_solvency {
+            headers.push("solvent");
+        }
+        if self.with_metadata {
+            headers.extend(["client_name", "currency"]);
+        }
+        if self.with_activity {
+            headers.extend([
+                "deposit_count",
+                "withdrawal_count",
+                "total_deposited",
+                "total_withdrawn",
+            ]);
+        }
+        headers
+            .iter()
+            .map(|name| self.header_case.apply(name))
+            .collect()
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            format: OutputFormat::default(),
+            display_scale: 4,
+            available_precision: None,
+            held_precision: None,
+            total_precision: None,
+            locked_format: BoolFormat::default(),
+            header_case: HeaderCase::default(),
+            with_solvency: false,
+            with_metadata: false,
+            with_activity: false,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Format in which accounts are written to the output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// CSV with a header row.
+    #[default]
+    Csv,
+    /// One JSON object per account per line, without a header.
+    Ndjson,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            _ => Err(anyhow!(
+                "Unknown output format '{}', expected one of: csv, ndjson.",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How disputes referencing a partially charged back transaction are handled.
+///
+/// A transaction is partially charged back if part of its dispute was resolved
+/// before the rest was charged back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartialChargebackPolicy {
+    /// Disputes on partially charged back transactions are ignored.
+    Ignore,
+    /// Only the part of the amount that wasn't charged back is disputed again.
+    /// Charging back that dispute adds to the charged back amount.
+    #[default]
+    DisputeRemainder,
+}
+
+impl FromStr for PartialChargebackPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(PartialChargebackPolicy::Ignore),
+            "dispute-remainder" => Ok(PartialChargebackPolicy::DisputeRemainder),
+            _ => Err(anyhow!(
+                "Unknown partial chargeback policy '{}', expected one of: ignore, dispute-remainder.",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,22 @@
+use std::error::Error;
+
+use super::TransactionRecord;
+
+/// A source of transaction records that can be processed on its own task,
+/// e.g. a CSV reader over a shard file.
+///
+/// This is implemented for all suitable iterators of records.
+pub trait RecordSource:
+    Iterator<Item = Result<TransactionRecord, Self::Error>> + Send + Sync + 'static
+{
+    /// Error produced while reading records.
+    type Error: Error + Send + Sync + 'static;
+}
+
+impl<I, E> RecordSource for I
+where
+    I: Iterator<Item = Result<TransactionRecord, E>> + Send + Sync + 'static,
+    E: Error + Send + Sync + 'static,
+{
+    type Error = E;
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+
+/// The reason a record was skipped without changing the account.
+///
+/// Displayed as a short snake_case code, e.g. `insufficient_funds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The client id is one of the configured reserved client ids.
+    ReservedClient,
+    /// The client isn't in the configured allowed clients.
+    ClientNotAllowed,
+    /// The source of the record isn't in the configured allowed sources.
+    SourceNotAllowed,
+    /// The status of the record isn't one of the applied statuses.
+    StatusNotApplied,
+    /// The first record of a client isn't a deposit, but an opening deposit is required.
+    NoOpeningDeposit,
+    /// The currency of the record differs from the currency of the account.
+    CurrencyMismatch,
+    /// The account is locked.
+    AccountLocked,
+    /// The dedup predicate rejected the tx id, or the tx id is already in the client's ledger.
+    Duplicate,
+    /// The withdrawal would leave less than the minimum balance.
+    InsufficientFunds,
+    /// The referenced transaction doesn't exist.
+    UnknownTransaction,
+    /// The referenced transaction isn't in a state the record applies to,
+    /// e.g. a resolve for an undisputed transaction.
+    InvalidDisputeState,
+    /// The referenced transaction has already been charged back.
+    AlreadyChargedBack,
+    /// A reversal referenced a transaction that isn't a deposit.
+    NotADeposit,
+    /// The account already has the maximum number of open disputes.
+    TooManyOpenDisputes,
+    /// Disputes on withdrawals are ignored by the configured policy.
+    WithdrawalDisputeIgnored,
+    /// A partial resolve tried to release more than is held.
+    ResolveExceedsHeldAmount,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let code = match self {
+            RejectReason::ReservedClient => "reserved_client",
+            RejectReason::ClientNotAllowed => "client_not_allowed",
+            RejectReason::SourceNotAllowed => "source_not_allowed",
+            RejectReason::StatusNotApplied => "status_not_applied",
+            RejectReason::NoOpeningDeposit => "no_opening_deposit",
+            RejectReason::CurrencyMismatch => "currency_mismatch",
+            RejectReason::AccountLocked => "account_locked",
+            RejectReason::Duplicate => "duplicate",
+            RejectReason::InsufficientFunds => "insufficient_funds",
+            RejectReason::UnknownTransaction => "unknown_transaction",
+            RejectReason::InvalidDisputeState => "invalid_dispute_state",
+            RejectReason::AlreadyChargedBack => "already_charged_back",
+            RejectReason::NotADeposit => "not_a_deposit",
+            RejectReason::TooManyOpenDisputes => "too_many_open_disputes",
+            RejectReason::WithdrawalDisputeIgnored => "withdrawal_dispute_ignored",
+            RejectReason::ResolveExceedsHeldAmount => "resolve_exceeds_held_amount",
+        };
+        write!(f, "{}", code)
+    }
+}
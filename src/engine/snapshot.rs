@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+
+use super::{Account, AccountActivity, EngineStats, TransactionDetails, TxId};
+
+/// Full state of a `TransactionEngine`, as written by `save_snapshot_bin`.
+///
+/// The binary format isn't self-describing, so fields can't be defaulted when loading.
+/// Snapshots can only be loaded by builds with the same snapshot layout.
+#[derive(Serialize, Deserialize)]
+pub(super) struct Snapshot {
+    pub accounts: Vec<AccountSnapshot>,
+    pub stats: EngineStats,
+}
+
+/// Full state of an `Account`, including its ledger.
+///
+/// `Account` itself serializes to the output format, so it can't be used for snapshots.
+#[derive(Serialize, Deserialize)]
+pub(super) struct AccountSnapshot {
+    tenant: Option<String>,
+    client_id: u16,
+    held_balance: f32,
+    available_balance: f32,
+    locked: bool,
+    transactions: HashMap<TxId, TransactionDetails>,
+    name: Option<String>,
+    currency: Option<String>,
+    ledger_order: VecDeque<TxId>,
+    activity: AccountActivity,
+}
+
+impl From<Account> for AccountSnapshot {
+    fn from(account: Account) -> Self {
+        AccountSnapshot {
+            tenant: account.tenant,
+            client_id: account.client_id,
+            held_balance: account.held_balance,
+            available_balance: account.available_balance,
+            locked: account.locked,
+            transactions: account.transactions,
+            name: account.name,
+            currency: account.currency,
+            ledger_order: account.ledger_order,
+            activity: account.activity,
+        }
+    }
+}
+
+impl From<AccountSnapshot> for Account {
+    fn from(snapshot: AccountSnapshot) -> Self {
+        let mut account = Account {
+            tenant: snapshot.tenant,
+            client_id: snapshot.client_id,
+            held_balance: snapshot.held_balance,
+            available_balance: snapshot.available_balance,
+            locked: snapshot.locked,
+            transactions: snapshot.transactions,
+            name: snapshot.name,
+            currency: snapshot.currency,
+            ledger_order: snapshot.ledger_order,
+            activity: snapshot.activity,
+            open_disputes: 0,
+        };
+        account.recount_open_disputes();
+        account
+    }
+}
@@ -1,520 +1,5421 @@
 use anyhow::{anyhow, Context, Result};
 use std::{
+    collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
     error::Error,
+    future::{self, Future},
+    io::{BufWriter, Read, Write},
     marker::{Send, Sync},
-    sync::{Arc, Mutex, RwLock},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
 };
 
-use super::{Account, TransactionDetails, TransactionRecord, TransactionType};
+use super::{
+    snapshot::Snapshot, Account, AccountActivity, AccountC, AccountKey, AccountStore,
+    AggregateBalances, DisputeState, EngineConfig, EngineError, EngineEvent, EngineStats,
+    EngineWarning, InMemoryAccountStore, OutputConfig, OutputFormat, PartialChargebackPolicy,
+    RecordSource, RejectReason, TransactionDetails, TransactionRecord, TransactionStatus,
+    TransactionType, TxId, WithdrawalDisputePolicy, WithdrawalOrder,
+};
 
-/// A small bridge between the `TransactionEngine` and the `Account`.
-///
-/// This allows the engine to find accounts by `client_id` without having to
-/// acquire locks for all accounts.
-struct AccountAccessor {
-    client_id: u16,
-    account: Arc<Mutex<Account>>,
+/// Number of finished accounts buffered between processing and writing in `process_and_stream_output`.
+const OUTPUT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Callback invoked with the running count of processed records.
+type ProgressCallback = Box<dyn Fn(usize) + Send + Sync>;
+
+/// Predicate deciding whether a deposit or withdrawal with the given tx id may be applied.
+type DedupPredicate = Box<dyn Fn(TxId) -> bool + Send + Sync>;
+
+/// Sink receiving the domain events emitted while processing.
+type EventSink = Box<dyn Fn(EngineEvent) + Send + Sync>;
+
+/// State of an account right before a record was applied, used to roll the record back.
+struct JournalEntry {
+    key: AccountKey,
+    tx_id: TxId,
+    available_balance: f32,
+    held_balance: f32,
+    locked: bool,
+    activity: AccountActivity,
+    open_disputes: usize,
+    name: Option<String>,
+    currency: Option<String>,
+    details: Option<TransactionDetails>,
+    /// Ledger entries evicted by the record, with their positions in the ledger order.
+    evicted: Vec<(usize, TxId, TransactionDetails)>,
+    /// Held amounts of disputed transactions, if the record may draw from them.
+    held_amounts: Vec<(TxId, f32)>,
 }
 
 /// The heart of the transaction processing logic.
-pub struct TransactionEngine {
-    accounts: RwLock<Vec<AccountAccessor>>,
+///
+/// The engine is generic over the `AccountStore` used to store accounts,
+/// which defaults to keeping all accounts in memory.
+pub struct TransactionEngine<S: AccountStore = InMemoryAccountStore> {
+    accounts: S,
+    config: EngineConfig,
+    dedup_predicate: Option<DedupPredicate>,
+    disputes_seen: AtomicBool,
+    event_sink: Option<EventSink>,
+    journal: Mutex<VecDeque<JournalEntry>>,
+    processed_records: AtomicUsize,
+    progress: Option<(usize, ProgressCallback)>,
+    results_read: AtomicBool,
+    stats_processed: AtomicU64,
+    stats_rejected: AtomicU64,
+    stats_by_source: Mutex<HashMap<String, u64>>,
+    stats_by_type: Mutex<HashMap<TransactionType, u64>>,
+    streaming: AtomicBool,
+    warnings: Mutex<Vec<EngineWarning>>,
+}
+
+impl Default for TransactionEngine {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransactionEngine {
     /// Construct a new `TransactionEngine`.
     pub fn new() -> Self {
+        Self::with_config(EngineConfig::default())
+    }
+
+    /// Construct a new `TransactionEngine` using the given config.
+    pub fn with_config(config: EngineConfig) -> Self {
+        Self::with_store(InMemoryAccountStore::default(), config)
+    }
+
+    /// Construct a new `TransactionEngine` with room for the given number of clients.
+    ///
+    /// This avoids reallocating the account index for inputs of known size,
+    /// but doesn't change the behavior in any way.
+    pub fn with_capacity(expected_clients: usize) -> Self {
+        Self::with_store(
+            InMemoryAccountStore::with_capacity(expected_clients),
+            EngineConfig::default(),
+        )
+    }
+}
+
+impl<S: AccountStore> TransactionEngine<S> {
+    /// Construct a new `TransactionEngine` using the given account store and config.
+    pub fn with_store(store: S, config: EngineConfig) -> Self {
         TransactionEngine {
-            accounts: RwLock::new(Vec::new()),
+            accounts: store,
+            config,
+            dedup_predicate: None,
+            disputes_seen: AtomicBool::new(false),
+            event_sink: None,
+            journal: Mutex::new(VecDeque::new()),
+            processed_records: AtomicUsize::new(0),
+            progress: None,
+            results_read: AtomicBool::new(false),
+            stats_processed: AtomicU64::new(0),
+            stats_rejected: AtomicU64::new(0),
+            stats_by_source: Mutex::new(HashMap::new()),
+            stats_by_type: Mutex::new(HashMap::new()),
+            streaming: AtomicBool::new(false),
+            warnings: Mutex::new(Vec::new()),
         }
     }
 
+    /// Invoke the given callback every `interval` processed records.
+    ///
+    /// The callback receives the running count of processed records.
+    /// It doesn't affect processing in any way.
+    pub fn with_progress_callback<F>(mut self, interval: usize, callback: F) -> Self
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.progress = Some((interval, Box::new(callback)));
+        self
+    }
+
+    /// Ask the given predicate before applying each deposit or withdrawal.
+    ///
+    /// The predicate receives the tx id and is called synchronously, e.g. to check the id
+    /// against an external deduplication service. Records it returns `false` for are skipped.
+    pub fn with_dedup_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(TxId) -> bool + Send + Sync + 'static,
+    {
+        self.dedup_predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// Pass an `EngineEvent` to the given sink for every change applied to an account.
+    ///
+    /// The sink is called synchronously while the affected account is locked,
+    /// so events of each account arrive in the order they were applied.
+    pub fn with_event_sink<F>(mut self, sink: F) -> Self
+    where
+        F: Fn(EngineEvent) + Send + Sync + 'static,
+    {
+        self.event_sink = Some(Box::new(sink));
+        self
+    }
+
     /// Process all transaction records from the given iterator.
+    ///
+    /// Records that can't be deserialized abort processing, unless `skip_unparsable_records` is set.
     pub async fn process_records<I, E>(&self, records: I) -> Result<()>
     where
         I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
         E: Error + Sync + Send + 'static,
     {
-        for record in records {
-            Self::process_transaction(&self.accounts, record?).await?
+        for (index, record) in records.enumerate() {
+            match record {
+                Ok(tx) => {
+                    self.process_record(tx).await?;
+                }
+                Err(err) => self.handle_unparsable_record(index as u64 + 1, err.into())?,
+            }
         }
 
         Ok(())
     }
 
-    /// Return a collection of all accounts.
+    /// Process all transaction records from the given iterator, unless the deadline passes.
     ///
-    /// This method should only be called after all `process_records` calls have ended.
-    /// It's very expensive, since it acquires locks for all accounts.
-    pub fn accounts(&self) -> Result<Vec<Account>> {
-        let accounts = self
-            .accounts
-            .read()
-            .map_err(|_| anyhow!("Unable to read accounts."))?;
-        Ok(accounts
-            .iter()
-            .map(|accessor| accessor.account.lock().unwrap().clone())
-            .collect())
+    /// The deadline is checked before reading every record. Once it has passed, an `EngineError::Timeout`
+    /// with the number of records processed by this call is returned. The remaining records
+    /// are not consumed, while all records processed up to that point stay applied.
+    pub async fn process_records_with_deadline<I, E>(
+        &self,
+        records: I,
+        deadline: Instant,
+    ) -> Result<()>
+    where
+        I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
+        E: Error + Sync + Send + 'static,
+    {
+        let mut records = records;
+        let mut position = 0;
+        let mut processed = 0;
+        while Instant::now() < deadline {
+            let record = match records.next() {
+                Some(record) => record,
+                None => return Ok(()),
+            };
+            position += 1;
+            match record {
+                Ok(tx) => {
+                    self.process_record(tx).await?;
+                    processed += 1;
+                }
+                Err(err) => self.handle_unparsable_record(position, err.into())?,
+            }
+        }
+
+        Err(EngineError::Timeout { processed }.into())
     }
 
-    /// Process a single transaction record.
-    async fn process_transaction(
-        accounts: &RwLock<Vec<AccountAccessor>>,
-        tx: TransactionRecord,
-    ) -> Result<()> {
-        // Validate transaction
-        if !tx.is_valid() {
-            return Err(anyhow!("Invalid transaction."));
+    /// Process CSV input from memory, e.g. for WASM or tests.
+    ///
+    /// The input must have a header row. Fields are trimmed and trailing fields may be omitted,
+    /// just like for files read by the CLI.
+    pub async fn process_csv_bytes(&self, bytes: &[u8]) -> Result<()> {
+        let reader = csv::ReaderBuilder::new()
+            .delimiter(b',')
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(bytes);
+        self.process_records(reader.into_deserialize()).await
+    }
+
+    /// Process raw CSV lines without a header.
+    ///
+    /// Each line is parsed positionally as `type,client,tx,amount`, where the amount may be omitted.
+    /// Empty lines are skipped. An error is returned for the first malformed line,
+    /// unless `skip_unparsable_records` is set.
+    pub async fn process_raw_lines<I>(&self, lines: I) -> Result<()>
+    where
+        I: Iterator<Item = String>,
+    {
+        let headers = csv::StringRecord::from(vec!["type", "client", "tx", "amount"]);
+        for (index, line) in lines.enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            // Parse the line like the main reader does, using the positional headers
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .trim(csv::Trim::All)
+                .from_reader(line.as_bytes());
+            let mut record = csv::StringRecord::new();
+            let parsed = reader
+                .read_record(&mut record)
+                .and_then(|_| record.deserialize::<TransactionRecord>(Some(&headers)));
+            match parsed {
+                Ok(tx) => {
+                    self.process_record(tx).await?;
+                }
+                Err(err) => self
+                    .handle_unparsable_record(index as u64 + 1, err.into())
+                    .with_context(|| format!("Unable to parse line {}.", index + 1))?,
+            }
         }
 
-        // Check whether the client already has an account
-        let client_exists = {
-            accounts
-                .read()
-                .map_err(|_| anyhow!("Unable to acquire read-lock on accounts."))?
-                .iter()
-                .any(|a| a.client_id == tx.client_id)
-        };
+        Ok(())
+    }
 
-        // Create account if it doesn't exist
-        if !client_exists {
-            let account = Account::new(tx.client_id);
-            let account_accessor = AccountAccessor {
-                client_id: tx.client_id,
-                account: Arc::new(Mutex::new(account)),
+    /// Process records grouped into batches, invoking `checkpoint` with the batch id
+    /// after each completed batch.
+    ///
+    /// Consecutive records with the same `batch` form a batch. Batches are applied atomically:
+    /// If a record of a batch fails, all changes of the batch are reverted and the error is returned,
+    /// so the engine is back at the state of the last checkpoint. This allows resuming from the
+    /// last completed batch, e.g. by saving a snapshot using `save_snapshot_bin` in `checkpoint`.
+    ///
+    /// Warnings and events of a reverted batch are kept, while the rollback journal is cleared.
+    /// No other records may be processed concurrently.
+    pub async fn process_batches<I, E, F>(&self, records: I, mut checkpoint: F) -> Result<()>
+    where
+        I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
+        E: Error + Sync + Send + 'static,
+        F: FnMut(Option<&str>) -> Result<()>,
+    {
+        let mut batch: Option<Option<String>> = None;
+        let mut undo: HashMap<AccountKey, Option<Account>> = HashMap::new();
+        let mut stats = self.stats();
+
+        for (index, record) in records.enumerate() {
+            let tx = match record {
+                Ok(tx) => tx,
+                Err(err) => {
+                    if let Err(err) = self.handle_unparsable_record(index as u64 + 1, err.into()) {
+                        self.revert_batch(undo, stats)?;
+                        return Err(err
+                            .context("Unable to process batch, reverted to the last checkpoint."));
+                    }
+                    continue;
+                }
             };
-            let mut accounts = accounts
-                .write()
-                .map_err(|_| anyhow!("Unable to acquire write-lock on accounts."))?;
-            accounts.push(account_accessor);
-        }
 
-        // Acquire read-lock on accounts
-        let read_only_accounts = accounts
-            .read()
-            .map_err(|_| anyhow!("Unable to acquire read-lock on accounts."))?;
+            // Checkpoint once the next batch begins
+            if let Some(current) = &batch {
+                if *current != tx.batch {
+                    checkpoint(current.as_deref())?;
+                    undo.clear();
+                    stats = self.stats();
+                }
+            }
+            batch = Some(tx.batch.clone());
 
-        // Find the account for the current transaction
-        let account_accessor = read_only_accounts
-            .iter()
-            .find(|a| a.client_id == tx.client_id)
-            .context("Unable to locale account by client id.")?;
+            // Remember the state of each account before the batch first touches it
+            if let Entry::Vacant(entry) = undo.entry(tx.account_key()) {
+                let account = match self.accounts.get(entry.key())? {
+                    Some(account) => Some(
+                        account
+                            .lock()
+                            .map_err(|_| anyhow!("Unable to acquire account reference."))?
+                            .clone(),
+                    ),
+                    None => None,
+                };
+                entry.insert(account);
+            }
 
-        // Acquire a lock on the account
-        let mut acc = account_accessor
-            .account
-            .lock()
-            .map_err(|_| anyhow!("Unable to acquire mutable account reference."))?;
+            if let Err(err) = self.process_record(tx).await {
+                self.revert_batch(undo, stats)?;
+                return Err(
+                    err.context("Unable to process batch, reverted to the last checkpoint.")
+                );
+            }
+        }
 
-        // Check if account is locked
-        if acc.locked {
-            // Don't process transaction and return
-            return Ok(());
+        // Checkpoint the last batch
+        if let Some(current) = batch {
+            checkpoint(current.as_deref())?;
         }
 
-        // Record transaction if it has an amount (deposit, withdrawal)
-        if let Some(amount) = tx.amount {
-            acc.transactions
-                .insert(tx.transaction_id, TransactionDetails::new(amount));
+        Ok(())
+    }
+
+    /// Handle a record at the given position that couldn't be deserialized.
+    ///
+    /// The error is returned, unless `skip_unparsable_records` is set.
+    /// In that case, the record is skipped and a warning is recorded instead.
+    pub fn handle_unparsable_record(&self, position: u64, err: anyhow::Error) -> Result<()> {
+        if !self.config.skip_unparsable_records {
+            return Err(err);
         }
+        self.warn(EngineWarning::UnparsableRecord {
+            position,
+            message: err.to_string(),
+        });
+        Ok(())
+    }
 
-        match tx.r#type {
-            // Handle deposit
-            TransactionType::Deposit => {
-                let amount = tx
-                    .amount
-                    .context("Unable to get amount from transaction.")?;
-                acc.available_balance += amount;
-            }
+    /// Process a single transaction record.
+    ///
+    /// Returns the reason if the record was skipped without changing the account.
+    pub async fn process_record(&self, tx: TransactionRecord) -> Result<Option<RejectReason>> {
+        let source = tx.source.clone();
+        let outcome = self.process_transaction(tx).await?;
+        self.record_processed();
+        self.record_source(source);
+        self.switch_to_streaming_if_due()?;
+        Ok(outcome)
+    }
 
-            // Handle withdrawal
-            TransactionType::Withdraw => {
-                let amount = tx
-                    .amount
-                    .context("Unable to get amount from transaction.")?;
+    /// Process multiple client-disjoint sources concurrently, each on its own task.
+    ///
+    /// Records of each source are processed in order, while different sources are processed in parallel.
+    /// This is only safe if no client appears in more than one source, since the relative order
+    /// of records from different sources is undefined. An error is returned if a client
+    /// appears in multiple sources, but records processed up to that point are not reverted.
+    /// After the first error, the remaining sources are stopped before this returns.
+    pub async fn process_sources<R>(self: &Arc<Self>, sources: Vec<R>) -> Result<()>
+    where
+        R: RecordSource,
+        S: 'static,
+    {
+        // Map every account to the index of the source it was first seen in
+        let owners: Arc<Mutex<HashMap<AccountKey, usize>>> = Arc::default();
 
-                // Check for sufficient funds
-                if (acc.available_balance - amount).is_sign_negative() {
-                    // Insufficient funds. Stop withdrawal but don't error out.
-                    return Ok(());
+        let mut tasks = Vec::with_capacity(sources.len());
+        for (index, source) in sources.into_iter().enumerate() {
+            let engine = Arc::clone(self);
+            let owners = Arc::clone(&owners);
+            tasks.push(tokio::spawn(async move {
+                let mut owned_accounts = HashSet::new();
+                for (position, record) in source.enumerate() {
+                    let tx = match record {
+                        Ok(tx) => tx,
+                        Err(err) => {
+                            engine.handle_unparsable_record(position as u64 + 1, err.into())?;
+                            continue;
+                        }
+                    };
+
+                    // Make sure no other source contains records of the same account
+                    let key = tx.account_key();
+                    if !owned_accounts.contains(&key) {
+                        let mut owners = owners
+                            .lock()
+                            .map_err(|_| anyhow!("Unable to acquire source owners."))?;
+                        let owner = *owners.entry(key.clone()).or_insert(index);
+                        if owner != index {
+                            return Err(anyhow!(
+                                "Client {} appears in sources {} and {}.",
+                                key.client_id,
+                                owner,
+                                index
+                            ));
+                        }
+                        owned_accounts.insert(key);
+                    }
+
+                    engine.process_record(tx).await?;
                 }
+                Ok(())
+            }));
+        }
+
+        // Wait for all sources, reporting the first error
+        let mut tasks = tasks.into_iter();
+        while let Some(task) = tasks.next() {
+            let err = match task.await {
+                Ok(Ok(())) => continue,
+                Ok(Err(err)) => err,
+                Err(err) => err.into(),
+            };
 
-                acc.available_balance -= amount;
+            // Stop the remaining sources and wait for them,
+            // so none of them changes the engine after returning
+            let remaining: Vec<_> = tasks.collect();
+            for task in &remaining {
+                task.abort();
+            }
+            for task in remaining {
+                let _ = task.await;
             }
+            return Err(err);
+        }
 
-            // Handle dispute
-            TransactionType::Dispute => {
-                // Find original transaction
-                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
-                    // This case is not listed in the specification, I'm assuming this is a no-op.
-                    if original_tx.disputed {
-                        return Ok(());
-                    }
+        Ok(())
+    }
 
-                    // Mark transaction as disputed
-                    acc.transactions
-                        .get_mut(&tx.transaction_id)
-                        .context("Unable to get transaction details.")?
-                        .disputed = true;
+    /// Process client-grouped, dispute-free records while keeping only a single account in memory.
+    ///
+    /// This is a constrained but very fast path for huge inputs. It requires that:
+    /// 1. The records contain no `dispute`, `resolve` or `chargeback` records.
+    /// 2. All records of a client are grouped together.
+    ///
+    /// Each client's final account is passed to `emit` as soon as the next client's records begin.
+    /// An error is returned if any of the preconditions are violated.
+    ///
+    /// Accounts processed this way are never stored in the engine.
+    pub async fn process_records_streaming<I, E, F>(&self, records: I, mut emit: F) -> Result<()>
+    where
+        I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
+        E: Error + Sync + Send + 'static,
+        F: FnMut(Account) -> Result<()>,
+    {
+        self.stream_records(records, |account| future::ready(emit(account)))
+            .await
+    }
 
-                    // Freeze transaction amount
-                    acc.available_balance -= original_tx.amount;
-                    acc.held_balance += original_tx.amount;
-                } else {
+    /// Process client-grouped, dispute-free records, awaiting `emit` with each finished account.
+    /// See `process_records_streaming`.
+    async fn stream_records<I, E, F, Fut>(&self, records: I, mut emit: F) -> Result<()>
+    where
+        I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
+        E: Error + Sync + Send + 'static,
+        F: FnMut(Account) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut current: Option<Account> = None;
+        let mut finished_clients = HashSet::new();
 
-                    // Transaction doesn't exist. As per specification,
-                    // this is an error on the partner side and should be ignored.
+        // Results are passed to the caller directly
+        self.mark_results_read();
+
+        for (index, record) in records.enumerate() {
+            let mut tx = match record {
+                Ok(tx) => tx,
+                Err(err) => {
+                    self.handle_unparsable_record(index as u64 + 1, err.into())?;
+                    continue;
                 }
+            };
+            tx.normalize_amount(self.config.amount_unit);
+
+            // Validate transaction
+            tx.validate().map_err(|reason| {
+                anyhow!("Invalid transaction {}: {}", tx.transaction_id, reason)
+            })?;
+            self.validate_precision(&tx)?;
+            self.validate_max_amount(&tx)?;
+            self.round_amount(&mut tx)?;
+
+            // Reject dispute-family records
+            if !matches!(
+                tx.r#type,
+                TransactionType::Deposit | TransactionType::Withdraw
+            ) {
+                return Err(anyhow!(
+                    "Streaming mode doesn't support dispute records (tx {}).",
+                    tx.transaction_id
+                ));
             }
+            self.record_source(tx.source.clone());
 
-            // Handle dispute resolution
-            TransactionType::Resolve => {
-                // Find original transaction
-                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
-                    // Ignore resolutions for undisputed transactions
-                    if !original_tx.disputed {
-                        return Ok(());
-                    }
+            // Skip records of reserved clients
+            if self.config.reserved_client_ids.contains(&tx.client_id) {
+                self.reject(RejectReason::ReservedClient)?;
+                self.record_processed();
+                continue;
+            }
 
-                    // Mark transaction as resolved
-                    acc.transactions
-                        .get_mut(&tx.transaction_id)
-                        .context("Unable to get transaction details.")?
-                        .disputed = false;
+            // Skip records of clients that aren't allowed
+            if !self.is_allowed_client(tx.client_id) {
+                self.reject(RejectReason::ClientNotAllowed)?;
+                self.record_processed();
+                continue;
+            }
 
-                    // Release transaction amount
-                    acc.available_balance += original_tx.amount;
-                    acc.held_balance -= original_tx.amount;
-                } else {
+            // Skip records of sources that aren't allowed
+            if !self.is_allowed_source(tx.source.as_deref()) {
+                self.reject(RejectReason::SourceNotAllowed)?;
+                self.record_processed();
+                continue;
+            }
 
-                    // Transaction doesn't exist. As per specification,
-                    // this is an error on the partner side and should be ignored.
+            // Skip records whose status isn't applied
+            if !self.is_applied_status(tx.status) {
+                self.reject(RejectReason::StatusNotApplied)?;
+                self.record_processed();
+                continue;
+            }
+
+            // Emit the previous account once the next client's records begin
+            if let Some(account) = current.take() {
+                if account.key() == tx.account_key() {
+                    current = Some(account);
+                } else {
+                    finished_clients.insert(account.key());
+                    emit(account).await?;
                 }
             }
 
-            // Handle chargeback
-            TransactionType::Chargeback => {
-                // Find original transaction
-                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
-                    // Ignore chargeback for undisputed transactions
-                    if !original_tx.disputed {
-                        return Ok(());
-                    }
+            // Make sure the input is grouped by client
+            if finished_clients.contains(&tx.account_key()) {
+                return Err(anyhow!(
+                    "Streaming mode requires input grouped by client, but client {} appeared again.",
+                    tx.client_id
+                ));
+            }
+
+            // Skip records that would open the account without a deposit
+            if current.is_none() && self.requires_opening_deposit(&tx) {
+                self.reject(RejectReason::NoOpeningDeposit)?;
+                self.record_processed();
+                continue;
+            }
 
-                    // Mark transaction as resolved
-                    acc.transactions
-                        .get_mut(&tx.transaction_id)
-                        .context("Unable to get transaction details.")?
-                        .disputed = false;
+            let acc = current.get_or_insert_with(|| Account::from_key(tx.account_key()));
+            self.apply_transaction(acc, tx)?;
 
-                    // Remove backcharged balance
-                    acc.held_balance -= original_tx.amount;
+            // Without disputes there's no need to keep the ledger around
+            acc.clear_ledger();
 
-                    // Lock account
-                    acc.locked = true;
-                } else {
+            self.record_processed();
+        }
 
-                    // Transaction doesn't exist. As per specification,
-                    // this is an error on the partner side and should be ignored.
-                }
-            }
+        // Emit the last account
+        if let Some(account) = current {
+            emit(account).await?;
         }
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::TransactionEngine;
-    use tokio::test;
+    /// Process client-grouped, dispute-free records and write each account while later clients are still processed.
+    ///
+    /// This has the same preconditions as `process_records_streaming`. Every finished account is sent
+    /// to a writer task over a bounded channel, so writing the output overlaps with processing the input,
+    /// while processing waits for the writer once a fixed number of accounts is pending, bounding memory.
+    /// The header row is always written, even if there are no accounts.
+    pub async fn process_and_stream_output<I, E, W>(
+        &self,
+        records: I,
+        config: &OutputConfig,
+        output: W,
+    ) -> Result<()>
+    where
+        I: Iterator<Item = std::result::Result<TransactionRecord, E>> + Sync + Send,
+        E: Error + Sync + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (sender, mut receiver) = tokio::sync::mpsc::channel::<Account>(OUTPUT_CHANNEL_CAPACITY);
 
-    // Helper macro to read transaction records from a string, process them and compare them to the expected output.
-    macro_rules! assert_csv_snapshot {
-        ($csv:expr => $expected:expr) => {{
-            // We can pretty much unwrap everything here, if anything goes wrong the test will fail.
-            // This is the correct behavior since any failure means something is seriously wrong.
-            let engine = TransactionEngine::new();
-            let input: String = $csv
-                .split_whitespace()
-                .map(|s| format!("{}\n", s))
-                .collect();
-            let expected_output: String = $expected
-                .split_whitespace()
-                .map(|s| format!("{}\n", s))
-                .collect();
-            let reader = csv::ReaderBuilder::new()
-                .delimiter(b',')
-                .flexible(true)
-                .trim(csv::Trim::All)
-                .from_reader(input.as_bytes());
-            engine
-                .process_records(reader.into_deserialize())
-                .await
-                .unwrap();
-            let mut accounts = engine.accounts().unwrap();
-            accounts.sort_by(|a, b| a.client_id.partial_cmp(&b.client_id).unwrap());
-            let mut output_writer = csv::WriterBuilder::new()
-                .delimiter(b',')
-                .has_headers(true)
-                .flexible(false)
-                .from_writer(vec![]);
-            for account in accounts {
-                output_writer.serialize(account).unwrap();
-            }
-            let output = String::from_utf8(output_writer.into_inner().unwrap()).unwrap();
-            if output != expected_output {
-                println!("Actual: {}", output);
+        // Write accounts on a blocking thread as soon as they arrive
+        let config = config.clone();
+        let writer = tokio::task::spawn_blocking(move || -> Result<()> {
+            match config.format {
+                OutputFormat::Csv => {
+                    let mut writer = csv::WriterBuilder::new()
+                        .has_headers(false)
+                        .from_writer(output);
+                    writer.write_record(config.headers())?;
+                    while let Some(account) = receiver.blocking_recv() {
+                        writer.serialize(account.output(&config))?;
+                    }
+                    writer.flush()?;
+                }
+                OutputFormat::Ndjson => {
+                    let mut output = BufWriter::new(output);
+                    while let Some(account) = receiver.blocking_recv() {
+                        let line = serde_json::to_string(&account.output(&config))
+                            .context("Unable to serialize account.")?;
+                        writeln!(output, "{}", line)?;
+                    }
+                    output.flush()?;
+                }
             }
-            assert!(output == expected_output);
-        }};
+            Ok(())
+        });
+
+        let sender_ref = &sender;
+        let processed = self
+            .stream_records(records, |account| async move {
+                sender_ref
+                    .send(account)
+                    .await
+                    .map_err(|_| anyhow!("Output writer stopped unexpectedly."))
+            })
+            .await;
+
+        // Close the channel so the writer finishes
+        drop(sender);
+
+        // Report writer errors first, since they make processing fail as well
+        writer.await??;
+        processed
     }
 
-    #[test]
-    async fn test_tx_deposits_one_client() {
-        assert_csv_snapshot!(
-            "
-                type,client,tx,amount
-                deposit,1,1,1.0
-                deposit,1,2,2.0
-                deposit,1,3,2.0
-                deposit,1,4,20.5
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,25.5,0.0,25.5,false
+    /// Return a collection of all accounts, sorted by tenant, then by client id.
+    ///
+    /// The order is deterministic, regardless of the order in which records were processed.
+    ///
+    /// This method should only be called after all `process_records` calls have ended.
+    /// It's very expensive, since it acquires locks for all accounts.
+    pub fn accounts(&self) -> Result<Vec<Account>> {
+        self.mark_results_read();
+        let mut accounts: Vec<Account> = self
+            .accounts
+            .iter()?
+            .map(|account| account.lock().unwrap().clone())
+            .collect();
+        accounts.sort_by(|a, b| (&a.tenant, a.client_id).cmp(&(&b.tenant, b.client_id)));
+        Ok(accounts)
+    }
+
+    /// Return all accounts of the given tenant as C-compatible structs with balances in integer cents,
+    /// sorted by client id.
+    ///
+    /// `AccountC` has no tenant, so accounts are returned per tenant, like by `tenant_accounts`.
+    pub fn accounts_ffi(&self, tenant: Option<&str>) -> Result<Vec<AccountC>> {
+        Ok(self
+            .tenant_accounts(tenant)?
+            .iter()
+            .map(AccountC::from)
+            .collect())
+    }
+
+    /// Return all accounts of the given tenant, sorted by client id.
+    ///
+    /// The default tenant is `None`. The same restrictions as for `accounts` apply.
+    pub fn tenant_accounts(&self, tenant: Option<&str>) -> Result<Vec<Account>> {
+        let mut accounts = self.accounts()?;
+        accounts.retain(|account| account.tenant.as_deref() == tenant);
+        Ok(accounts)
+    }
+
+    /// Finish processing and return all final accounts, sorted by tenant, then by client id.
+    ///
+    /// This consumes the engine, so no more records can be processed afterwards.
+    /// The transaction ledgers are dropped, since they can't be referenced anymore.
+    pub fn finalize(self) -> Result<Vec<Account>> {
+        let mut accounts = self.accounts()?;
+        for account in &mut accounts {
+            account.clear_ledger();
+        }
+        Ok(accounts)
+    }
+
+    /// Return the number of accounts of all tenants, without copying them.
+    pub fn len(&self) -> Result<usize> {
+        self.mark_results_read();
+        self.accounts.len()
+    }
+
+    /// Whether no account exists yet.
+    pub fn is_empty(&self) -> Result<bool> {
+        self.mark_results_read();
+        self.accounts.is_empty()
+    }
+
+    /// Return a copy of the account for the given client of the default tenant, if it exists.
+    pub fn account(&self, client_id: u16) -> Result<Option<Account>> {
+        self.with_account(client_id, Account::clone)
+    }
+
+    /// Run `f` with the locked account for the given client of the default tenant, if it exists,
+    /// returning its result without copying the account.
+    ///
+    /// The account stays locked while `f` runs, so it should be cheap.
+    pub fn with_account<R>(
+        &self,
+        client_id: u16,
+        f: impl FnOnce(&Account) -> R,
+    ) -> Result<Option<R>> {
+        self.mark_results_read();
+        Ok(self
+            .accounts
+            .get(&AccountKey::new(None, client_id))?
+            .map(|account| f(&account.lock().unwrap())))
+    }
+
+    /// Return all clients whose total balance exceeds the given threshold.
+    ///
+    /// The result contains `(client_id, total)` pairs, sorted descending by total.
+    pub fn clients_above(&self, threshold: f32) -> Result<Vec<(u16, f32)>> {
+        self.mark_results_read();
+        let mut clients: Vec<(u16, f32)> = self
+            .accounts
+            .iter()?
+            .filter_map(|account| {
+                let account = account.lock().unwrap();
+                let total = account.total_balance();
+                (total > threshold).then(|| (account.client_id, total))
+            })
+            .collect();
+        clients.sort_by(|a, b| b.1.total_cmp(&a.1));
+        Ok(clients)
+    }
+
+    /// Return the currently disputed transactions of all clients, e.g. for a "funds on hold" statement.
+    ///
+    /// The result maps each client with open disputes to `(tx_id, held_amount)` pairs, sorted by tx id.
+    /// Clients of all tenants are merged.
+    pub fn held_breakdown(&self) -> Result<HashMap<u16, Vec<(TxId, f32)>>> {
+        self.mark_results_read();
+        let mut breakdown: HashMap<u16, Vec<(TxId, f32)>> = HashMap::new();
+        for account in self.accounts.iter()? {
+            let account = account.lock().unwrap();
+            let held = account
+                .transactions
+                .iter()
+                .filter(|(_, details)| details.is_disputed())
+                .map(|(tx_id, details)| (*tx_id, details.held_amount));
+            breakdown.entry(account.client_id).or_default().extend(held);
+        }
+        breakdown.retain(|_, held| !held.is_empty());
+        for held in breakdown.values_mut() {
+            held.sort_by_key(|(tx_id, _)| *tx_id);
+        }
+        Ok(breakdown)
+    }
+
+    /// Return how much the total balance of each client changed compared to the given baseline,
+    /// e.g. the totals at the time a snapshot was saved.
+    ///
+    /// Clients missing from the baseline start at zero, while baseline clients without an account
+    /// end at zero. Clients of all tenants are merged.
+    pub fn deltas_since(&self, baseline: &HashMap<u16, f32>) -> Result<HashMap<u16, f32>> {
+        self.mark_results_read();
+        let mut deltas: HashMap<u16, f32> = baseline
+            .iter()
+            .map(|(client_id, total)| (*client_id, -total))
+            .collect();
+        for account in self.accounts.iter()? {
+            let account = account.lock().unwrap();
+            *deltas.entry(account.client_id).or_default() += account.total_balance();
+        }
+        Ok(deltas)
+    }
+
+    /// Return the balances of all accounts summed up. Clients of all tenants are included.
+    pub fn aggregate_balances(&self) -> Result<AggregateBalances> {
+        self.mark_results_read();
+        let mut aggregate = AggregateBalances::default();
+        for account in self.accounts.iter()? {
+            let account = account.lock().unwrap();
+            aggregate.total_available += f64::from(account.available_balance);
+            aggregate.total_held += f64::from(account.held_balance);
+        }
+        Ok(aggregate)
+    }
+
+    /// Check the aggregate total balance against the configured `assert_zero_sum`, e.g. after processing a closed system.
+    ///
+    /// Records a warning and returns false if the aggregate isn't within tolerance.
+    /// Always returns true if no check is configured.
+    pub fn check_zero_sum(&self) -> Result<bool> {
+        let check = match self.config.assert_zero_sum {
+            Some(check) => check,
+            None => return Ok(true),
+        };
+        let actual = self.aggregate_balances()?.total();
+        if check.holds(actual) {
+            return Ok(true);
+        }
+        self.warn(EngineWarning::ZeroSumViolated {
+            expected: check.expected,
+            actual,
+        });
+        Ok(false)
+    }
+
+    /// Return a stable hash of the final account state, e.g. to verify that runs on different machines agree.
+    ///
+    /// The hash covers tenant, client id, balances and lock state of all accounts, sorted like `accounts`,
+    /// with balances formatted to four decimal places. It doesn't depend on the processing order,
+    /// the platform or the Rust version. The same restrictions as for `accounts` apply.
+    pub fn state_hash(&self) -> Result<u64> {
+        // 64-bit FNV-1a, since the hashers of the standard library aren't guaranteed to be stable
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for account in self.accounts()? {
+            let line = format!(
+                "{},{},{:.4},{:.4},{:.4},{}\n",
+                account.tenant.as_deref().unwrap_or_default(),
+                account.client_id,
+                account.available_balance,
+                account.held_balance,
+                account.total_balance(),
+                account.locked
+            );
+            for byte in line.bytes() {
+                hash ^= u64::from(byte);
+                hash = hash.wrapping_mul(0x0100_0000_01b3);
+            }
+        }
+        Ok(hash)
+    }
+
+    /// Lock all accounts matching the given predicate, e.g. for incident response.
+    ///
+    /// Returns the number of accounts that were locked by this call.
+    /// Each account is locked while the predicate is evaluated, so it can't change in between.
+    pub fn lock_where<F>(&self, predicate: F) -> Result<usize>
+    where
+        F: Fn(&Account) -> bool,
+    {
+        self.set_locked_where(predicate, true)
+    }
+
+    /// Unlock all accounts matching the given predicate.
+    ///
+    /// Returns the number of accounts that were unlocked by this call.
+    pub fn unlock_where<F>(&self, predicate: F) -> Result<usize>
+    where
+        F: Fn(&Account) -> bool,
+    {
+        self.set_locked_where(predicate, false)
+    }
+
+    /// Set the lock state of all accounts matching the given predicate,
+    /// returning the number of accounts that changed.
+    fn set_locked_where<F>(&self, predicate: F, locked: bool) -> Result<usize>
+    where
+        F: Fn(&Account) -> bool,
+    {
+        Ok(self
+            .accounts
+            .iter()?
+            .filter(|account| {
+                let mut account = account.lock().unwrap();
+                if account.locked == locked || !predicate(&account) {
+                    return false;
+                }
+                account.locked = locked;
+                true
+            })
+            .count())
+    }
+
+    /// Write the per-transaction ledger of all accounts as CSV.
+    ///
+    /// Rows have the format `client,tx,amount,disputed` and are sorted by client, then by tx.
+    pub fn export_ledger<W: Write>(&self, w: W) -> Result<()> {
+        self.mark_results_read();
+
+        // Collect all ledger entries
+        let mut entries = Vec::new();
+        for account in self.accounts.iter()? {
+            let account = account
+                .lock()
+                .map_err(|_| anyhow!("Unable to acquire account reference."))?;
+            entries.extend(
+                account
+                    .transactions
+                    .iter()
+                    .map(|(tx_id, details)| (account.client_id, *tx_id, details.clone())),
+            );
+        }
+        entries.sort_by_key(|(client_id, tx_id, _)| (*client_id, *tx_id));
+
+        // Write ledger entries
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(b',')
+            .has_headers(false)
+            .flexible(false)
+            .from_writer(w);
+        writer.write_record(["client", "tx", "amount", "disputed"])?;
+        for (client_id, tx_id, details) in entries {
+            writer.write_record([
+                client_id.to_string(),
+                tx_id.to_string(),
+                format!("{:.4}", details.amount),
+                details.is_disputed().to_string(),
+            ])?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Write all accounts, including their ledgers, and the statistics in a compact binary format.
+    ///
+    /// The rollback journal isn't included.
+    /// This should not be called while records are being processed.
+    pub fn save_snapshot_bin<W: Write>(&self, w: W) -> Result<()> {
+        let snapshot = Snapshot {
+            accounts: self.accounts()?.into_iter().map(Into::into).collect(),
+            stats: self.stats(),
+        };
+        bincode::serialize_into(w, &snapshot).context("Unable to write snapshot.")
+    }
+
+    /// Restore all accounts and the statistics from a snapshot written by `save_snapshot_bin`.
+    ///
+    /// Accounts of the snapshot replace existing accounts with the same key.
+    /// This should not be called while records are being processed.
+    pub fn load_snapshot_bin<R: Read>(&self, r: R) -> Result<()> {
+        let snapshot: Snapshot =
+            bincode::deserialize_from(r).context("Unable to read snapshot.")?;
+
+        // Replace accounts with their snapshot
+        for account in snapshot.accounts {
+            let account = Account::from(account);
+            let handle = self.accounts.get_or_create(&account.key())?;
+            *handle
+                .lock()
+                .map_err(|_| anyhow!("Unable to acquire mutable account reference."))? = account;
+        }
+
+        // Restore statistics
+        self.stats_processed
+            .store(snapshot.stats.processed, Ordering::Relaxed);
+        self.stats_rejected
+            .store(snapshot.stats.rejected, Ordering::Relaxed);
+        *self
+            .stats_by_type
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire stats."))? = snapshot.stats.by_type;
+        *self
+            .stats_by_source
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire stats."))? = snapshot.stats.by_source;
+
+        Ok(())
+    }
+
+    /// Roll back the last `n` records applied by `process_records`.
+    ///
+    /// Returns the number of records that were actually rolled back, which is lower than `n`
+    /// if the journal contains fewer records. Only the last `journal_size` applied records
+    /// are journaled, rejected records don't change any account and are never journaled.
+    ///
+    /// Rolling back restores the balances, the locked flag, the account metadata, the ledger entry
+    /// of the referenced transaction and any ledger entries evicted by the record,
+    /// so disputes, resolves and chargebacks are reverted exactly as well.
+    /// However, some effects are not reverted:
+    /// - Accounts created by a rolled back record remain, with their initial balances.
+    /// - Stats, warnings and progress are not affected.
+    /// - Records processed in streaming mode are never journaled.
+    ///
+    /// This should not be called while records are being processed.
+    pub fn rollback(&self, n: usize) -> Result<usize> {
+        let mut journal = self
+            .journal
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire journal."))?;
+
+        let mut rolled_back = 0;
+        while rolled_back < n {
+            let entry = match journal.pop_back() {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            // Restore the previous account state
+            let account = self
+                .accounts
+                .get(&entry.key)?
+                .context("Unable to find journaled account.")?;
+            let mut acc = account
+                .lock()
+                .map_err(|_| anyhow!("Unable to acquire mutable account reference."))?;
+            acc.available_balance = entry.available_balance;
+            acc.held_balance = entry.held_balance;
+            acc.locked = entry.locked;
+            acc.activity = entry.activity;
+            acc.open_disputes = entry.open_disputes;
+            acc.name = entry.name;
+            acc.currency = entry.currency;
+            for (tx_id, held_amount) in entry.held_amounts {
+                if let Some(details) = acc.transactions.get_mut(&tx_id) {
+                    details.held_amount = held_amount;
+                }
+            }
+
+            // Restore evicted entries first, in case the record evicted its own entry
+            for (position, tx_id, details) in entry.evicted.into_iter().rev() {
+                acc.restore_ledger_entry(position, tx_id, details);
+            }
+            match entry.details {
+                Some(details) => acc.transactions.insert(entry.tx_id, details),
+                None => acc.remove_transaction(entry.tx_id),
+            };
+
+            rolled_back += 1;
+        }
+
+        Ok(rolled_back)
+    }
+
+    /// Return all warnings encountered while processing.
+    pub fn warnings(&self) -> Vec<EngineWarning> {
+        self.warnings.lock().unwrap().clone()
+    }
+
+    /// Return the current statistics.
+    pub fn stats(&self) -> EngineStats {
+        EngineStats {
+            processed: self.stats_processed.load(Ordering::Relaxed),
+            rejected: self.stats_rejected.load(Ordering::Relaxed),
+            by_type: self.stats_by_type.lock().unwrap().clone(),
+            by_source: self.stats_by_source.lock().unwrap().clone(),
+        }
+    }
+
+    /// Return the current statistics and reset them to zero.
+    ///
+    /// Each counter is swapped atomically, so increments happening concurrently
+    /// are either part of the returned stats or of the next ones, but never lost.
+    pub fn take_stats(&self) -> EngineStats {
+        EngineStats {
+            processed: self.stats_processed.swap(0, Ordering::Relaxed),
+            rejected: self.stats_rejected.swap(0, Ordering::Relaxed),
+            by_type: std::mem::take(&mut *self.stats_by_type.lock().unwrap()),
+            by_source: std::mem::take(&mut *self.stats_by_source.lock().unwrap()),
+        }
+    }
+
+    /// Record a warning.
+    fn warn(&self, warning: EngineWarning) {
+        self.warnings.lock().unwrap().push(warning);
+    }
+
+    /// Restore the accounts and statistics from before a failed batch.
+    fn revert_batch(
+        &self,
+        undo: HashMap<AccountKey, Option<Account>>,
+        stats: EngineStats,
+    ) -> Result<()> {
+        for (key, account) in undo {
+            match account {
+                Some(account) => {
+                    let handle = self.accounts.get_or_create(&key)?;
+                    *handle
+                        .lock()
+                        .map_err(|_| anyhow!("Unable to acquire mutable account reference."))? =
+                        account;
+                }
+                None => self.accounts.remove(&key)?,
+            }
+        }
+        self.stats_processed
+            .store(stats.processed, Ordering::Relaxed);
+        self.stats_rejected.store(stats.rejected, Ordering::Relaxed);
+        *self
+            .stats_by_type
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire stats."))? = stats.by_type;
+        *self
+            .stats_by_source
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire stats."))? = stats.by_source;
+
+        // Journaled records of the batch don't exist anymore
+        self.journal
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire journal."))?
+            .clear();
+
+        Ok(())
+    }
+
+    /// Check whether the given record may only be applied to existing accounts,
+    /// since accounts have to be opened with a deposit.
+    fn requires_opening_deposit(&self, tx: &TransactionRecord) -> bool {
+        self.config.require_opening_deposit && tx.r#type != TransactionType::Deposit
+    }
+
+    /// Return the order in which withdrawals of the given client draw from the balances.
+    fn withdrawal_order(&self, client_id: u16) -> WithdrawalOrder {
+        match &self.config.withdrawal_order_clients {
+            Some(clients) if !clients.contains(&client_id) => WithdrawalOrder::AvailableFirst,
+            _ => self.config.withdrawal_order,
+        }
+    }
+
+    /// Remember that the results were read, see `unread_results_warning`.
+    fn mark_results_read(&self) {
+        self.results_read.store(true, Ordering::Relaxed);
+    }
+
+    /// Return a warning if records were processed, but the results were never read.
+    #[cfg(debug_assertions)]
+    fn unread_results_warning(&self) -> Option<String> {
+        let processed = self.processed_records.load(Ordering::Relaxed);
+        (processed > 0 && !self.results_read.load(Ordering::Relaxed)).then(|| {
+            format!(
+                "TransactionEngine dropped after processing {} records, but the accounts were never read.",
+                processed
+            )
+        })
+    }
+
+    /// Pass an event to the event sink, if any.
+    fn emit(&self, event: EngineEvent) {
+        if let Some(sink) = &self.event_sink {
+            sink(event);
+        }
+    }
+
+    /// Count a processed record and report progress if necessary.
+    fn record_processed(&self) {
+        self.stats_processed.fetch_add(1, Ordering::Relaxed);
+        let count = self.processed_records.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some((interval, callback)) = &self.progress {
+            if count.is_multiple_of(*interval) {
+                callback(count);
+            }
+        }
+    }
+
+    /// Whether the engine switched to streaming after crossing the `auto_stream_threshold`.
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.load(Ordering::Relaxed)
+    }
+
+    /// Switch to streaming once the `auto_stream_threshold` is crossed without any disputes,
+    /// dropping the ledgers of all accounts.
+    fn switch_to_streaming_if_due(&self) -> Result<()> {
+        let threshold = match self.config.auto_stream_threshold {
+            Some(threshold) => threshold,
+            None => return Ok(()),
+        };
+        if self.processed_records.load(Ordering::Relaxed) < threshold
+            || self.disputes_seen.load(Ordering::Relaxed)
+            || self.streaming.swap(true, Ordering::Relaxed)
+        {
+            return Ok(());
+        }
+
+        for account in self.accounts.iter()? {
+            account
+                .lock()
+                .map_err(|_| anyhow!("Unable to acquire account reference."))?
+                .clear_ledger();
+        }
+        Ok(())
+    }
+
+    /// Count a processed record of the given source, if it has one.
+    fn record_source(&self, source: Option<String>) {
+        if let Some(source) = source {
+            *self
+                .stats_by_source
+                .lock()
+                .unwrap()
+                .entry(source)
+                .or_default() += 1;
+        }
+    }
+
+    /// Count an applied record of the given type.
+    fn record_applied(&self, tx_type: TransactionType) {
+        *self
+            .stats_by_type
+            .lock()
+            .unwrap()
+            .entry(tx_type)
+            .or_default() += 1;
+    }
+
+    /// Count a record that was ignored without changing the account.
+    fn reject(&self, reason: RejectReason) -> Result<Option<RejectReason>> {
+        self.stats_rejected.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(reason))
+    }
+
+    /// Return the `(reversed, held)` amounts of disputing the given transaction,
+    /// or `None` if the transaction can't be disputed.
+    ///
+    /// The reversed amount is credited to the available balance, the held amount
+    /// is moved from the available to the held balance.
+    /// Resolving the dispute reverts both, a chargeback removes the held amount.
+    fn dispute_effect(&self, details: &TransactionDetails) -> Option<(f32, f32)> {
+        let amount = details.amount;
+        match (details.tx_type, self.config.withdrawal_dispute_policy) {
+            (TransactionType::Withdraw, WithdrawalDisputePolicy::Ignore) => None,
+            (TransactionType::Withdraw, WithdrawalDisputePolicy::ReverseToAvailable) => {
+                Some((amount, 0.0))
+            }
+            _ => Some((0.0, amount)),
+        }
+    }
+
+    /// Check whether a dispute may re-open the given charged back transaction.
+    ///
+    /// This is only the case for partially charged back transactions,
+    /// if the configured policy allows disputing the remainder.
+    fn disputes_remainder(&self, details: &TransactionDetails) -> bool {
+        self.config.partial_chargeback_policy == PartialChargebackPolicy::DisputeRemainder
+            && details.dispute_state == DisputeState::ChargedBack
+            && details.charged_back_amount > 0.0
+            && details.charged_back_amount < details.amount
+    }
+
+    /// Move an amount between the available and held balance of the given account,
+    /// returning an `EngineError::Overflow` if either balance would overflow.
+    fn transition_held(tx: &TransactionRecord, acc: &mut Account, delta: f32) -> Result<()> {
+        if acc.transition_held(delta) {
+            Ok(())
+        } else {
+            Err(EngineError::Overflow {
+                client_id: tx.client_id,
+                tx_id: tx.transaction_id,
+            }
+            .into())
+        }
+    }
+
+    /// Add two amounts, returning an `EngineError::Overflow` instead of a non-finite result.
+    fn checked_add(tx: &TransactionRecord, a: f32, b: f32) -> Result<f32> {
+        Self::check_overflow(tx, a + b)
+    }
+
+    /// Subtract two amounts, returning an `EngineError::Overflow` instead of a non-finite result.
+    fn checked_sub(tx: &TransactionRecord, a: f32, b: f32) -> Result<f32> {
+        Self::check_overflow(tx, a - b)
+    }
+
+    /// Make sure the result of a balance mutation is still finite.
+    fn check_overflow(tx: &TransactionRecord, value: f32) -> Result<f32> {
+        if value.is_finite() {
+            Ok(value)
+        } else {
+            Err(EngineError::Overflow {
+                client_id: tx.client_id,
+                tx_id: tx.transaction_id,
+            }
+            .into())
+        }
+    }
+
+    /// Check whether records of the given source may be processed.
+    fn is_allowed_source(&self, source: Option<&str>) -> bool {
+        match &self.config.allowed_sources {
+            Some(allowed_sources) => source.is_some_and(|source| allowed_sources.contains(source)),
+            None => true,
+        }
+    }
+
+    /// Check whether records of the given client may be processed.
+    fn is_allowed_client(&self, client_id: u16) -> bool {
+        match &self.config.allowed_clients {
+            Some(allowed_clients) => allowed_clients.contains(&client_id),
+            None => true,
+        }
+    }
+
+    /// Check whether records with the given status are applied.
+    fn is_applied_status(&self, status: Option<TransactionStatus>) -> bool {
+        match (status, &self.config.applied_statuses) {
+            (None, _) => true,
+            (Some(status), Some(applied_statuses)) => applied_statuses.contains(&status),
+            (Some(status), None) => status == TransactionStatus::Posted,
+        }
+    }
+
+    /// Round the amount to the configured ingest precision, half away from zero.
+    ///
+    /// The rounded record is validated again, so amounts rounding to zero are rejected.
+    fn round_amount(&self, tx: &mut TransactionRecord) -> Result<()> {
+        if let Some(places) = self.config.ingest_rounding {
+            // Round in double precision, so the result is the closest amount to the exact value
+            let factor = 10f64.powi(places as i32);
+            tx.amount = tx
+                .amount
+                .map(|amount| ((amount as f64 * factor).round() / factor) as f32);
+            tx.validate().map_err(|reason| {
+                anyhow!(
+                    "Invalid transaction {} after rounding: {}",
+                    tx.transaction_id,
+                    reason
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Make sure the amount doesn't exceed the precision configured for its transaction type.
+    fn validate_precision(&self, tx: &TransactionRecord) -> Result<()> {
+        let max_places = match tx.r#type {
+            TransactionType::Deposit => self.config.deposit_precision,
+            TransactionType::Withdraw => self.config.withdrawal_precision,
+            _ => None,
+        };
+        if let (Some(max_places), Some(places)) = (max_places, tx.decimal_places()) {
+            if places > max_places {
+                return Err(anyhow!(
+                    "Amount of tx {} exceeds the maximum of {} decimal places.",
+                    tx.transaction_id,
+                    max_places
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure the amount doesn't exceed the configured maximum transaction amount.
+    fn validate_max_amount(&self, tx: &TransactionRecord) -> Result<()> {
+        if let (Some(max_amount), Some(amount)) = (self.config.max_transaction_amount, tx.amount) {
+            if amount > max_amount {
+                return Err(anyhow!(
+                    "Amount of tx {} exceeds the maximum transaction amount of {}.",
+                    tx.transaction_id,
+                    max_amount
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Process a single transaction record, returning the reason if it was skipped.
+    async fn process_transaction(&self, mut tx: TransactionRecord) -> Result<Option<RejectReason>> {
+        tx.normalize_amount(self.config.amount_unit);
+
+        // Validate transaction
+        tx.validate()
+            .map_err(|reason| anyhow!("Invalid transaction {}: {}", tx.transaction_id, reason))?;
+        self.validate_precision(&tx)?;
+        self.validate_max_amount(&tx)?;
+        self.round_amount(&mut tx)?;
+
+        // Disputes need the ledger, which is dropped once the engine switched to streaming
+        if !matches!(
+            tx.r#type,
+            TransactionType::Deposit | TransactionType::Withdraw
+        ) {
+            if self.is_streaming() {
+                return Err(EngineError::DisputeAfterStreaming {
+                    client_id: tx.client_id,
+                    tx_id: tx.transaction_id,
+                }
+                .into());
+            }
+            self.disputes_seen.store(true, Ordering::Relaxed);
+        }
+
+        // Reject records of reserved clients, before touching any account
+        if self.config.reserved_client_ids.contains(&tx.client_id) {
+            return self.reject(RejectReason::ReservedClient);
+        }
+
+        // Ignore records of clients that aren't allowed, before touching any account
+        if !self.is_allowed_client(tx.client_id) {
+            return self.reject(RejectReason::ClientNotAllowed);
+        }
+
+        // Ignore records of sources that aren't allowed
+        if !self.is_allowed_source(tx.source.as_deref()) {
+            return self.reject(RejectReason::SourceNotAllowed);
+        }
+
+        // Ignore records whose status isn't applied, e.g. pending ones
+        if !self.is_applied_status(tx.status) {
+            return self.reject(RejectReason::StatusNotApplied);
+        }
+
+        // Only deposits may create accounts, if an opening deposit is required
+        if self.requires_opening_deposit(&tx) && self.accounts.get(&tx.account_key())?.is_none() {
+            return self.reject(RejectReason::NoOpeningDeposit);
+        }
+
+        // Records referencing a transaction have nothing to reference for unknown clients
+        if !matches!(
+            tx.r#type,
+            TransactionType::Deposit | TransactionType::Withdraw
+        ) && self.accounts.get(&tx.account_key())?.is_none()
+        {
+            return self.reject(RejectReason::UnknownTransaction);
+        }
+
+        // Find or create the account for the current transaction
+        let account = self.accounts.get_or_create(&tx.account_key())?;
+
+        // Acquire a lock on the account
+        let mut acc = account
+            .lock()
+            .map_err(|_| anyhow!("Unable to acquire mutable account reference."))?;
+
+        // Point disputes without a tx id to the latest disputable deposit,
+        // before journaling, so a rollback restores the transaction actually disputed
+        if self.config.dispute_latest_deposit
+            && tx.r#type == TransactionType::Dispute
+            && tx.transaction_id == TxId::from(0)
+        {
+            if let Some(tx_id) = acc.latest_disputable_deposit() {
+                tx.transaction_id = tx_id;
+            }
+        }
+
+        // Remember the previous account state if journaling is enabled
+        let entry = (self.config.journal_size > 0).then(|| JournalEntry {
+            key: acc.key(),
+            tx_id: tx.transaction_id,
+            available_balance: acc.available_balance,
+            held_balance: acc.held_balance,
+            locked: acc.locked,
+            activity: acc.activity,
+            open_disputes: acc.open_disputes,
+            name: acc.name.clone(),
+            currency: acc.currency.clone(),
+            details: acc.transactions.get(&tx.transaction_id).cloned(),
+            evicted: Vec::new(),
+            held_amounts: if tx.r#type == TransactionType::Withdraw
+                && self.withdrawal_order(tx.client_id) == WithdrawalOrder::HeldFirst
+            {
+                acc.transactions
+                    .iter()
+                    .filter(|(_, details)| details.is_disputed())
+                    .map(|(tx_id, details)| (*tx_id, details.held_amount))
+                    .collect()
+            } else {
+                Vec::new()
+            },
+        });
+
+        let is_transfer = matches!(
+            tx.r#type,
+            TransactionType::Deposit | TransactionType::Withdraw
+        );
+        let outcome = self.apply_transaction(&mut acc, tx)?;
+
+        // Bound the ledger, keeping the evicted entries for the journal
+        let evicted = match self.config.max_ledger_entries {
+            Some(max_entries) if is_transfer => acc.evict_ledger_entries(max_entries),
+            _ => Vec::new(),
+        };
+
+        // Without disputes there's no need to keep the ledger around
+        if self.is_streaming() {
+            acc.clear_ledger();
+        }
+
+        // Journal the applied record while still holding the account lock,
+        // so the journal order matches the order of changes per account.
+        if let (Some(mut entry), None) = (entry, outcome) {
+            entry.evicted = evicted;
+            let mut journal = self
+                .journal
+                .lock()
+                .map_err(|_| anyhow!("Unable to acquire journal."))?;
+            if journal.len() == self.config.journal_size {
+                journal.pop_front();
+            }
+            journal.push_back(entry);
+        }
+
+        Ok(outcome)
+    }
+
+    /// Apply a single, already validated transaction record to the given account,
+    /// returning the reason if it was skipped.
+    fn apply_transaction(
+        &self,
+        acc: &mut Account,
+        tx: TransactionRecord,
+    ) -> Result<Option<RejectReason>> {
+        // Pre-allocate the ledger of new accounts
+        if acc.transactions.is_empty() {
+            acc.transactions.reserve(self.config.ledger_capacity);
+        }
+
+        // Skip records in a different currency than the account
+        if acc.currency.is_some() && tx.currency.is_some() && tx.currency != acc.currency {
+            return self.reject(RejectReason::CurrencyMismatch);
+        }
+
+        // Check if account is locked, optionally still letting disputes or resolves through.
+        // Reversals move available funds just like transfers, so they're always ignored,
+        // unless all records are applied regardless of the lock state in replay mode.
+        let was_locked = acc.locked;
+        let is_transfer = matches!(
+            tx.r#type,
+            TransactionType::Deposit | TransactionType::Withdraw
+        );
+        let allowed_on_locked = tx.r#type != TransactionType::Reversal
+            && (self.config.process_disputes_on_locked
+                || (self.config.resolve_on_locked && tx.r#type == TransactionType::Resolve));
+        if was_locked && !self.config.replay_mode && (is_transfer || !allowed_on_locked) {
+            // Don't process transaction and return
+            return self.reject(RejectReason::AccountLocked);
+        }
+
+        // Remember the total balance to detect crossing the ceiling
+        let previous_total = acc.total_balance();
+
+        // Skip deposits and withdrawals rejected by the dedup predicate
+        if let Some(predicate) = &self.dedup_predicate {
+            if is_transfer && !predicate(tx.transaction_id) {
+                return self.reject(RejectReason::Duplicate);
+            }
+        }
+
+        // Skip deposits and withdrawals colliding with a tx id in the ledger,
+        // e.g. because an upstream generator wrapped around.
+        // Ledgers are kept per client and disputes only look up the client's own ledger,
+        // so an id reused by another client can't overwrite or be confused with anything.
+        // Checking the client's ledger therefore catches every collision that matters,
+        // without keeping a global set of all ids ever seen.
+        if is_transfer && acc.transactions.contains_key(&tx.transaction_id) {
+            return self.reject(RejectReason::Duplicate);
+        }
+
+        match tx.r#type {
+            // Handle deposit
+            TransactionType::Deposit => {
+                let amount = tx
+                    .amount
+                    .context("Unable to get amount from transaction.")?;
+                acc.available_balance = Self::checked_add(&tx, acc.available_balance, amount)?;
+                acc.activity.deposit_count += 1;
+                acc.activity.total_deposited += amount;
+
+                // Record transaction
+                acc.record_transaction(
+                    tx.transaction_id,
+                    TransactionDetails::new(tx.r#type, amount),
+                );
+                self.emit(EngineEvent::Deposited {
+                    client_id: tx.client_id,
+                    tx_id: tx.transaction_id,
+                    amount,
+                });
+            }
+
+            // Handle withdrawal
+            TransactionType::Withdraw => {
+                let amount = tx
+                    .amount
+                    .context("Unable to get amount from transaction.")?;
+
+                // Determine the part drawn from held funds, if the client draws from them first
+                let from_held = match self.withdrawal_order(tx.client_id) {
+                    WithdrawalOrder::AvailableFirst => 0.0,
+                    WithdrawalOrder::HeldFirst => amount.min(acc.held_balance.max(0.0)),
+                };
+                let from_available = amount - from_held;
+
+                // Check for sufficient funds, retaining the minimum balance
+                if acc.available_balance - from_available < self.config.minimum_balance {
+                    // Insufficient funds. Stop withdrawal but don't error out.
+                    return self.reject(RejectReason::InsufficientFunds);
+                }
+
+                let held_balance = Self::checked_sub(&tx, acc.held_balance, from_held)?;
+                acc.available_balance =
+                    Self::checked_sub(&tx, acc.available_balance, from_available)?;
+                acc.held_balance = held_balance;
+                acc.draw_from_disputes(from_held);
+                acc.activity.withdrawal_count += 1;
+                acc.activity.total_withdrawn += amount;
+
+                // Record transaction only after a successful withdrawal,
+                // so rejected withdrawals can't be disputed later on.
+                acc.record_transaction(
+                    tx.transaction_id,
+                    TransactionDetails::new(tx.r#type, amount),
+                );
+                self.emit(EngineEvent::Withdrawn {
+                    client_id: tx.client_id,
+                    tx_id: tx.transaction_id,
+                    amount,
+                });
+            }
+
+            // Handle dispute
+            TransactionType::Dispute => {
+                // Find original transaction
+                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
+                    // Disputes for already disputed or charged back transactions aren't
+                    // listed in the specification, I'm assuming these are no-ops.
+                    // The remainder of partially charged back transactions may be disputed again.
+                    let next_state = match original_tx.dispute_state.transition(&tx.r#type) {
+                        Some(next_state) => next_state,
+                        None if self.disputes_remainder(&original_tx) => DisputeState::Disputed,
+                        None => return self.reject(RejectReason::InvalidDisputeState),
+                    };
+
+                    // Ignore disputes exceeding the limit of open disputes
+                    if let Some(max_open_disputes) = self.config.max_open_disputes {
+                        if acc.open_disputes() >= max_open_disputes {
+                            return self.reject(RejectReason::TooManyOpenDisputes);
+                        }
+                    }
+
+                    // Determine how the dispute affects the balances.
+                    // Charged back funds are gone, so only the rest can be held again.
+                    let (reversed, held) = match self.dispute_effect(&original_tx) {
+                        Some((reversed, held)) => {
+                            (reversed, held - original_tx.charged_back_amount)
+                        }
+                        None => return self.reject(RejectReason::WithdrawalDisputeIgnored),
+                    };
+
+                    // Credit reversed funds and freeze the held transaction amount.
+                    // At most one of them is non-zero, so a failure never leaves a partial update.
+                    acc.available_balance =
+                        Self::checked_add(&tx, acc.available_balance, reversed)?;
+                    Self::transition_held(&tx, acc, held)?;
+
+                    // Mark transaction as disputed and remember the held amount
+                    let details = acc
+                        .set_dispute_state(tx.transaction_id, next_state)
+                        .context("Unable to get transaction details.")?;
+                    details.held_amount = held;
+                    self.emit(EngineEvent::DisputeOpened {
+                        client_id: tx.client_id,
+                        tx_id: tx.transaction_id,
+                        amount: reversed + held,
+                    });
+                } else {
+                    // Transaction doesn't exist. As per specification,
+                    // this is an error on the partner side and should be ignored.
+                    return self.reject(RejectReason::UnknownTransaction);
+                }
+            }
+
+            // Handle dispute resolution
+            TransactionType::Resolve => {
+                // Find original transaction
+                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
+                    // Charged back funds are gone and must never be released again
+                    if original_tx.dispute_state == DisputeState::ChargedBack {
+                        self.warn(EngineWarning::ResolveAfterChargeback {
+                            client_id: tx.client_id,
+                            tx_id: tx.transaction_id,
+                        });
+                        return self.reject(RejectReason::AlreadyChargedBack);
+                    }
+
+                    // Ignore resolutions for undisputed transactions
+                    let next_state = match original_tx.dispute_state.transition(&tx.r#type) {
+                        Some(next_state) => next_state,
+                        None => return self.reject(RejectReason::InvalidDisputeState),
+                    };
+
+                    // Determine the released and reversed amounts
+                    let held_amount = original_tx.held_amount;
+                    let (released, reversed, next_state) = match tx.amount {
+                        // Releasing more than is held is rejected
+                        Some(amount) if amount > held_amount => {
+                            return self.reject(RejectReason::ResolveExceedsHeldAmount)
+                        }
+
+                        // Partially release the held amount, the transaction stays disputed
+                        Some(amount) if amount < held_amount => {
+                            (amount, 0.0, original_tx.dispute_state)
+                        }
+
+                        // Release the remaining held amount and undo the rest of the dispute
+                        _ => match self.dispute_effect(&original_tx) {
+                            Some((reversed, _)) => (held_amount, reversed, next_state),
+                            None => return self.reject(RejectReason::WithdrawalDisputeIgnored),
+                        },
+                    };
+
+                    // Release the held amount and take back reversed funds.
+                    // At most one of them is non-zero, so a failure never leaves a partial update.
+                    Self::transition_held(&tx, acc, -released)?;
+                    acc.available_balance =
+                        Self::checked_sub(&tx, acc.available_balance, reversed)?;
+
+                    // Mark transaction as resolved, unless it was only partially resolved
+                    let details = acc
+                        .set_dispute_state(tx.transaction_id, next_state)
+                        .context("Unable to get transaction details.")?;
+                    details.held_amount = held_amount - released;
+                    self.emit(EngineEvent::Resolved {
+                        client_id: tx.client_id,
+                        tx_id: tx.transaction_id,
+                        amount: released + reversed,
+                    });
+                } else {
+                    // Transaction doesn't exist. As per specification,
+                    // this is an error on the partner side and should be ignored.
+                    return self.reject(RejectReason::UnknownTransaction);
+                }
+            }
+
+            // Handle chargeback
+            TransactionType::Chargeback => {
+                // Find original transaction
+                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
+                    // Ignore chargeback for undisputed transactions
+                    let next_state = match original_tx.dispute_state.transition(&tx.r#type) {
+                        Some(next_state) => next_state,
+                        None => return self.reject(RejectReason::InvalidDisputeState),
+                    };
+
+                    // Only the part that is still held is charged back
+                    let held_balance =
+                        Self::checked_sub(&tx, acc.held_balance, original_tx.held_amount)?;
+
+                    // Mark transaction as charged back
+                    let details = acc
+                        .set_dispute_state(tx.transaction_id, next_state)
+                        .context("Unable to get transaction details.")?;
+                    details.charged_back_amount += original_tx.held_amount;
+                    details.held_amount = 0.0;
+
+                    // Remove backcharged balance
+                    acc.held_balance = held_balance;
+                    self.emit(EngineEvent::ChargedBack {
+                        client_id: tx.client_id,
+                        tx_id: tx.transaction_id,
+                        amount: original_tx.held_amount,
+                    });
+
+                    // Lock account
+                    acc.locked = true;
+                } else {
+                    // Transaction doesn't exist. As per specification,
+                    // this is an error on the partner side and should be ignored.
+                    return self.reject(RejectReason::UnknownTransaction);
+                }
+            }
+
+            // Handle reversal
+            TransactionType::Reversal => {
+                // Find original transaction
+                if let Some(original_tx) = acc.transactions.get(&tx.transaction_id).cloned() {
+                    // Only deposits can be reversed
+                    if original_tx.tx_type != TransactionType::Deposit {
+                        return self.reject(RejectReason::NotADeposit);
+                    }
+
+                    // Ignore reversals for disputed, charged back or already reversed transactions
+                    let next_state = match original_tx.dispute_state.transition(&tx.r#type) {
+                        Some(next_state) => next_state,
+                        None => return self.reject(RejectReason::InvalidDisputeState),
+                    };
+
+                    // Take back the deposited amount, unless it would overdraw the account
+                    let available_balance =
+                        Self::checked_sub(&tx, acc.available_balance, original_tx.amount)?;
+                    if available_balance < 0.0 && !self.config.allow_overdrawing_reversals {
+                        return self.reject(RejectReason::InsufficientFunds);
+                    }
+                    acc.available_balance = available_balance;
+
+                    // Mark transaction as reversed, so it can't be disputed anymore
+                    acc.set_dispute_state(tx.transaction_id, next_state)
+                        .context("Unable to get transaction details.")?;
+                    self.emit(EngineEvent::Reversed {
+                        client_id: tx.client_id,
+                        tx_id: tx.transaction_id,
+                        amount: original_tx.amount,
+                    });
+                } else {
+                    // Transaction doesn't exist, this is an error on the partner side
+                    return self.reject(RejectReason::UnknownTransaction);
+                }
+            }
+        }
+
+        // Lock account if the available balance became negative
+        if self.config.auto_lock_on_negative && acc.available_balance.is_sign_negative() {
+            acc.locked = true;
+        }
+
+        // Report the account being locked by this transaction
+        if !was_locked && acc.locked {
+            self.emit(EngineEvent::AccountLocked {
+                client_id: tx.client_id,
+                tx_id: tx.transaction_id,
+            });
+        }
+
+        // Warn if the total balance crossed the ceiling
+        if let Some(ceiling) = self.config.total_balance_ceiling {
+            let total = acc.total_balance();
+            if previous_total <= ceiling && total > ceiling {
+                self.warn(EngineWarning::TotalBalanceCeilingExceeded {
+                    client_id: tx.client_id,
+                    tx_id: tx.transaction_id,
+                    total,
+                });
+            }
+        }
+
+        // Capture account holder metadata of applied records, the first seen values win
+        if acc.name.is_none() {
+            acc.name = tx.client_name;
+        }
+        if acc.currency.is_none() {
+            acc.currency = tx.currency;
+        }
+
+        self.record_applied(tx.r#type);
+        Ok(None)
+    }
+}
+
+/// Warn about forgotten results in debug builds, see `unread_results_warning`.
+#[cfg(debug_assertions)]
+impl<S: AccountStore> Drop for TransactionEngine<S> {
+    fn drop(&mut self) {
+        if let Some(warning) = self.unread_results_warning() {
+            report_unread_results(warning);
+        }
+    }
+}
+
+/// Print a warning about forgotten results.
+#[cfg(all(debug_assertions, not(test)))]
+fn report_unread_results(warning: String) {
+    eprintln!("Warning: {}", warning);
+}
+
+#[cfg(all(debug_assertions, test))]
+thread_local! {
+    /// Warnings about forgotten results of engines dropped on this thread, captured for tests.
+    static UNREAD_RESULTS_WARNINGS: std::cell::RefCell<Vec<String>> = Default::default();
+}
+
+/// Capture a warning about forgotten results instead of printing it.
+#[cfg(all(debug_assertions, test))]
+fn report_unread_results(warning: String) {
+    UNREAD_RESULTS_WARNINGS.with(|warnings| warnings.borrow_mut().push(warning));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{
+        Account, AccountActivity, AccountHandle, AccountKey, AccountStore, AggregateBalances,
+        AmountUnit, DisputeState, EngineConfig, EngineError, EngineEvent, EngineStats,
+        EngineWarning, InMemoryAccountStore, OutputConfig, PartialChargebackPolicy, RejectReason,
+        TransactionDetails, TransactionRecord, TransactionStatus, TransactionType, TxId,
+        WithdrawalDisputePolicy, WithdrawalOrder, ZeroSumCheck,
+    };
+    use crate::TransactionEngine;
+    use std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+        time::{Duration, Instant},
+    };
+    use tokio::test;
+
+    // Helper function to process transaction records from a string.
+    async fn engine_from_csv(csv: &str) -> TransactionEngine {
+        let engine = TransactionEngine::new();
+        let input: String = csv.split_whitespace().map(|s| format!("{}\n", s)).collect();
+        let reader = csv::ReaderBuilder::new()
+            .delimiter(b',')
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(input.as_bytes());
+        engine
+            .process_records(reader.into_deserialize())
+            .await
+            .unwrap();
+        engine
+    }
+
+    // Helper macro to read transaction records from a string, process them and compare them to the expected output.
+    macro_rules! assert_csv_snapshot {
+        ($config:expr, $csv:expr => $expected:expr) => {{
+            // We can pretty much unwrap everything here, if anything goes wrong the test will fail.
+            // This is the correct behavior since any failure means something is seriously wrong.
+            let engine = TransactionEngine::with_config($config);
+            let input: String = $csv
+                .split_whitespace()
+                .map(|s| format!("{}\n", s))
+                .collect();
+            let expected_output: String = $expected
+                .split_whitespace()
+                .map(|s| format!("{}\n", s))
+                .collect();
+            let reader = csv::ReaderBuilder::new()
+                .delimiter(b',')
+                .flexible(true)
+                .trim(csv::Trim::All)
+                .from_reader(input.as_bytes());
+            engine
+                .process_records(reader.into_deserialize())
+                .await
+                .unwrap();
+            let accounts = engine.accounts().unwrap();
+            let mut output_writer = csv::WriterBuilder::new()
+                .delimiter(b',')
+                .has_headers(false)
+                .flexible(false)
+                .from_writer(vec![]);
+            output_writer
+                .write_record(OutputConfig::default().headers())
+                .unwrap();
+            for account in accounts {
+                output_writer.serialize(account).unwrap();
+            }
+            let output = String::from_utf8(output_writer.into_inner().unwrap()).unwrap();
+            if output != expected_output {
+                println!("Actual: {}", output);
+            }
+            assert!(output == expected_output);
+        }};
+        ($csv:expr => $expected:expr) => {{
+            assert_csv_snapshot!(EngineConfig::default(), $csv => $expected)
+        }};
+    }
+
+    #[test]
+    async fn test_tx_deposits_one_client() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,1.0
+                deposit,1,2,2.0
+                deposit,1,3,2.0
+                deposit,1,4,20.5
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,25.5000,0.0000,25.5000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_deposits_withdrawals_positive_balance() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                withdrawal,1,3,15.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,20.0000,0.0000,20.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_deposits_withdrawals_zero_balance() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                withdrawal,1,3,35.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,0.0000,0.0000,0.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_deposits_withdrawals_negative_balance() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                withdrawal,1,3,40.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,35.0000,0.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_minimum_balance() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                minimum_balance: 10.0,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,20.0
+                withdrawal,1,2,15.0
+                withdrawal,1,3,10.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_on_rejected_withdrawal() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,20.0
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,25.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_of_held_funds() {
+        // Disputed funds are held, so they can't be withdrawn
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                withdrawal,1,2,50.0
+            " =>
+            "
+                client,available,held,total,locked
+                1,0.0000,100.0000,100.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_held_first() {
+        let config = EngineConfig {
+            withdrawal_order: WithdrawalOrder::HeldFirst,
+            withdrawal_order_clients: Some([1].into_iter().collect()),
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,60.0
+                deposit,1,2,40.0
+                dispute,1,2,
+                withdrawal,1,3,30.0
+                withdrawal,1,4,20.0
+                deposit,2,5,60.0
+                deposit,2,6,40.0
+                dispute,2,6,
+                withdrawal,2,7,30.0
+            " =>
+            "
+                client,available,held,total,locked
+                1,50.0000,0.0000,50.0000,false
+                2,30.0000,40.0000,70.0000,false
+            "
+        );
+
+        // Resolving the dispute afterwards only releases what's still held
+        let config = EngineConfig {
+            withdrawal_order: WithdrawalOrder::HeldFirst,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,60.0
+                deposit,1,2,40.0
+                dispute,1,2,
+                withdrawal,1,3,30.0
+                resolve,1,2,
+            " =>
+            "
+                client,available,held,total,locked
+                1,70.0000,0.0000,70.0000,false
+            "
+        );
+
+        // Charging back withdrawn held funds doesn't leave a negative held balance
+        let config = EngineConfig {
+            withdrawal_order: WithdrawalOrder::HeldFirst,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                dispute,1,1,
+                withdrawal,1,2,10.0
+                chargeback,1,1,
+            " =>
+            "
+                client,available,held,total,locked
+                1,0.0000,0.0000,0.0000,true
+            "
+        );
+
+        // Rolling back such a withdrawal restores the held amounts
+        let engine = TransactionEngine::with_config(EngineConfig {
+            withdrawal_order: WithdrawalOrder::HeldFirst,
+            journal_size: 1,
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    dispute,1,1,
+                    withdrawal,1,2,4.0
+                ",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            engine.account(1).unwrap().unwrap().transactions[&TxId::from(1)].held_amount,
+            6.0
+        );
+        engine.rollback(1).unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.held_balance, 10.0);
+        assert_eq!(account.transactions[&TxId::from(1)].held_amount, 10.0);
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_of_exact_available_with_held_funds() {
+        // Withdrawing exactly the available funds succeeds, the held funds stay untouched
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,60.0
+                deposit,1,2,40.0
+                dispute,1,2,
+                withdrawal,1,3,60.0
+            " =>
+            "
+                client,available,held,total,locked
+                1,0.0000,40.0000,40.0000,false
+            "
+        );
+
+        // Anything above the available funds is rejected
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,60.0
+                deposit,1,2,40.0
+                dispute,1,2,
+                withdrawal,1,3,60.0001
+            " =>
+            "
+                client,available,held,total,locked
+                1,60.0000,40.0000,100.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_reversal() {
+        // The reversed deposit can't be disputed afterwards
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                reversal,1,2,
+                dispute,1,2,
+                reversal,1,2,
+            " =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,false
+            "
+        );
+
+        // Withdrawals and disputed deposits can't be reversed
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,5.0
+                reversal,1,2,
+                dispute,1,1,
+                reversal,1,1,
+            " =>
+            "
+                client,available,held,total,locked
+                1,-5.0000,10.0000,5.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_overdrawing_reversal() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,8.0
+                reversal,1,1,
+            " =>
+            "
+                client,available,held,total,locked
+                1,2.0000,0.0000,2.0000,false
+            "
+        );
+
+        let config = EngineConfig {
+            allow_overdrawing_reversals: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,8.0
+                reversal,1,1,
+            " =>
+            "
+                client,available,held,total,locked
+                1,-8.0000,0.0000,-8.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_max_open_disputes() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                max_open_disputes: Some(2),
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                deposit,1,3,40.0
+                dispute,1,1,
+                dispute,1,2,
+                dispute,1,3,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,40.0000,30.0000,70.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_max_open_disputes_after_closing() {
+        // Resolved disputes no longer count towards the limit
+        assert_csv_snapshot!(
+            EngineConfig {
+                max_open_disputes: Some(1),
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                deposit,1,3,40.0
+                dispute,1,1,
+                resolve,1,1,
+                dispute,1,2,
+                resolve,1,2,
+                dispute,1,3,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,30.0000,40.0000,70.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_rollback_open_disputes() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 10,
+            max_open_disputes: Some(1),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,1,2,20.0
+                    dispute,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Rolling back the dispute frees its slot for another one
+        assert_eq!(engine.rollback(1).unwrap(), 1);
+        assert_eq!(engine.account(1).unwrap().unwrap().open_disputes(), 0);
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    dispute,1,2,
+                ",
+            ))
+            .await
+            .unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.held_balance, 20.0);
+        assert_eq!(account.open_disputes(), 1);
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_dispute_ignore() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                withdrawal_dispute_policy: WithdrawalDisputePolicy::Ignore,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,70.0000,0.0000,70.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_dispute_hold() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                withdrawal_dispute_policy: WithdrawalDisputePolicy::Hold,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,40.0000,30.0000,70.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_dispute_reverse_to_available() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                withdrawal_dispute_policy: WithdrawalDisputePolicy::ReverseToAvailable,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,100.0000,0.0000,100.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_dispute_reversal_resolved() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                withdrawal_dispute_policy: WithdrawalDisputePolicy::ReverseToAvailable,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+                dispute,1,2,
+                resolve,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,70.0000,0.0000,70.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_withdrawal_dispute_reversal_charged_back() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                withdrawal_dispute_policy: WithdrawalDisputePolicy::ReverseToAvailable,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+                dispute,1,2,
+                chargeback,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,100.0000,0.0000,100.0000,true
+            "
+        )
+    }
+
+    // Precision rules for a brokerage feed with fractional share deposits.
+    fn brokerage_config() -> EngineConfig {
+        EngineConfig {
+            deposit_precision: Some(8),
+            withdrawal_precision: Some(2),
+            ..EngineConfig::default()
+        }
+    }
+
+    #[test]
+    async fn test_deposit_precision_allowed() {
+        let engine = TransactionEngine::with_config(brokerage_config());
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,0.12345678
+                    withdrawal,1,2,0.12
+                ",
+            ))
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(engine.account(1).unwrap().unwrap().transactions.len(), 2);
+    }
+
+    #[test]
+    async fn test_withdrawal_precision_rejected() {
+        let engine = TransactionEngine::with_config(brokerage_config());
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    withdrawal,1,2,0.12345678
+                ",
+            ))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 10.0);
+    }
+
+    #[test]
+    async fn test_precision_of_large_amounts() {
+        // Places beyond the precision of `f32` are still counted
+        let engine = TransactionEngine::with_config(brokerage_config());
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,200000.0
+                    withdrawal,1,2,100000.001
+                ",
+            ))
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            engine.account(1).unwrap().unwrap().available_balance,
+            200000.0
+        );
+
+        let engine = TransactionEngine::with_config(brokerage_config());
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,1.123456789
+                ",
+            ))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn test_max_transaction_amount() {
+        let config = EngineConfig {
+            max_transaction_amount: Some(1000.0),
+            ..EngineConfig::default()
+        };
+
+        // Amounts up to the limit are processed
+        let engine = TransactionEngine::with_config(config.clone());
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,999.5
+                    withdrawal,1,2,500.0
+                ",
+            ))
+            .await;
+        assert!(result.is_ok());
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 499.5);
+
+        // Amounts above the limit are rejected
+        let engine = TransactionEngine::with_config(config);
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,1000.5
+                ",
+            ))
+            .await;
+        assert!(result.is_err());
+        assert!(engine.account(1).unwrap().is_none());
+    }
+
+    #[test]
+    async fn test_amount_in_cents() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                amount_unit: AmountUnit::Cents,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount_cents
+                deposit,1,1,1250
+                deposit,1,2,99
+                withdrawal,1,3,300
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,9.5000,0.9900,10.4900,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_with_invalid_dispute() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,3,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,35.0000,0.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_with_resolution() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                deposit,1,3,10.0
+                resolve,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,45.0000,0.0000,45.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_redispute_after_resolution() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                resolve,1,2,
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,25.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_max_ledger_entries() {
+        let config = EngineConfig {
+            max_ledger_entries: Some(2),
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                deposit,1,3,30.0
+                dispute,1,1,
+                dispute,1,3,
+            " =>
+            "
+                client,available,held,total,locked
+                1,30.0000,30.0000,60.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_partial_resolve() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,30.0000,70.0000,100.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_keeps_total_balance() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                withdrawal,1,2,30.0
+            ",
+        )
+        .await;
+        let total_before = engine.account(1).unwrap().unwrap().total_balance();
+
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    dispute,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, -30.0);
+        assert_eq!(account.held_balance, 100.0);
+        assert_eq!(account.total_balance(), total_before);
+    }
+
+    #[test]
+    async fn test_tx_partial_resolves_until_fully_resolved() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                resolve,1,1,70.0
+            ",
+        )
+        .await;
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 100.0);
+        assert_eq!(account.held_balance, 0.0);
+        assert_eq!(
+            account.transactions[&TxId::from(1)].dispute_state,
+            DisputeState::Resolved
+        );
+    }
+
+    #[test]
+    async fn test_tx_full_resolve_after_partial_resolve() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                resolve,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,100.0000,0.0000,100.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_partial_resolve_exceeding_held_amount() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                resolve,1,1,80.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,30.0000,70.0000,100.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_chargeback_after_partial_resolve() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                chargeback,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,30.0000,0.0000,30.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_chargeback_keeps_other_held_funds() {
+        // Only the remaining held part of tx 1 is charged back, tx 2 stays held
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                deposit,1,2,50.0
+                dispute,1,1,
+                dispute,1,2,
+                resolve,1,1,30.0
+                resolve,1,1,20.0
+                chargeback,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,50.0000,50.0000,100.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_dispute_latest_deposit() {
+        let config = EngineConfig {
+            dispute_latest_deposit: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                withdrawal,1,3,5.0
+                dispute,1,0,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,5.0000,20.0000,25.0000,false
+            "
+        );
+
+        // Disputes may omit the tx id entirely
+        let config = EngineConfig {
+            dispute_latest_deposit: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                dispute,1,,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,20.0000,30.0000,false
+            "
+        );
+
+        // Already disputed deposits are skipped
+        let config = EngineConfig {
+            dispute_latest_deposit: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,20.0
+                dispute,1,2,
+                dispute,1,0,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,0.0000,30.0000,30.0000,false
+            "
+        );
+
+        // Without the setting, tx 0 is just an unknown transaction
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                dispute,1,0,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_dispute_after_partial_chargeback() {
+        // Only the resolved remainder of 30 is disputed again
+        let config = EngineConfig {
+            process_disputes_on_locked: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                chargeback,1,1,
+                dispute,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,0.0000,30.0000,30.0000,true
+            "
+        );
+
+        // Charging back the remainder as well makes the chargeback complete
+        let config = EngineConfig {
+            process_disputes_on_locked: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                chargeback,1,1,
+                dispute,1,1,
+                chargeback,1,1,
+                dispute,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,0.0000,0.0000,0.0000,true
+            "
+        );
+
+        // The remainder can't be disputed again using the ignore policy
+        let config = EngineConfig {
+            process_disputes_on_locked: true,
+            partial_chargeback_policy: PartialChargebackPolicy::Ignore,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,100.0
+                dispute,1,1,
+                resolve,1,1,30.0
+                chargeback,1,1,
+                dispute,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,30.0000,0.0000,30.0000,true
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_dispute_with_invalid_resolution() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                deposit,1,3,10.0
+                resolve,1,4,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,20.0000,25.0000,45.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_undisputed_resolution() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                resolve,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,35.0000,0.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_with_chargeback() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                chargeback,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_after_chargeback() {
+        // Charged back funds are gone, so disputing again must not hold them
+        let config = EngineConfig {
+            process_disputes_on_locked: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                chargeback,1,2,
+                dispute,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_resolve_after_chargeback() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                chargeback,1,2,
+                resolve,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_resolve_after_chargeback_on_unlocked_account() {
+        let engine = TransactionEngine::new();
+
+        // Simulate an account that has been unlocked after a chargeback
+        let mut account = Account::new(1);
+        account.available_balance = 10.0;
+        account.transactions.insert(
+            2.into(),
+            TransactionDetails {
+                tx_type: TransactionType::Deposit,
+                amount: 25.0,
+                held_amount: 0.0,
+                charged_back_amount: 25.0,
+                dispute_state: DisputeState::ChargedBack,
+            },
+        );
+
+        engine
+            .apply_transaction(
+                &mut account,
+                TransactionRecord {
+                    r#type: TransactionType::Resolve,
+                    client_id: 1,
+                    transaction_id: 2.into(),
+                    amount: None,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert_eq!(account.available_balance, 10.0);
+        assert_eq!(account.held_balance, 0.0);
+        assert_eq!(
+            engine.warnings(),
+            vec![EngineWarning::ResolveAfterChargeback {
+                client_id: 1,
+                tx_id: 2.into()
+            }]
+        );
+    }
+
+    #[test]
+    async fn test_auto_stream_threshold() {
+        let config = EngineConfig {
+            auto_stream_threshold: Some(3),
+            ..EngineConfig::default()
+        };
+        let engine = TransactionEngine::with_config(config.clone());
+        let mut records = records_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,2,2,20.0
+                withdrawal,1,3,2.5
+                deposit,1,4,5.0
+                dispute,1,1,
+            ",
+        )
+        .map(Result::unwrap);
+
+        for tx in records.by_ref().take(2) {
+            engine.process_record(tx).await.unwrap();
+        }
+        assert!(!engine.is_streaming());
+
+        // Crossing the threshold drops all ledgers
+        for tx in records.by_ref().take(2) {
+            engine.process_record(tx).await.unwrap();
+        }
+        assert!(engine.is_streaming());
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 12.5);
+        assert!(account.transactions.is_empty());
+
+        // Disputes can't be processed anymore
+        let err = engine
+            .process_record(records.next().unwrap())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::DisputeAfterStreaming {
+                client_id: 1,
+                tx_id: 1.into()
+            })
+        );
+
+        // Disputes before crossing the threshold prevent the switch
+        let engine = TransactionEngine::with_config(config);
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    dispute,1,1,
+                    resolve,1,1,
+                    deposit,1,2,5.0
+                ",
+            ))
+            .await
+            .unwrap();
+        assert!(!engine.is_streaming());
+        assert_eq!(engine.account(1).unwrap().unwrap().transactions.len(), 2);
+    }
+
+    #[test]
+    async fn test_replay_mode() {
+        let config = EngineConfig {
+            replay_mode: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,5.0
+                dispute,1,2,
+                chargeback,1,2,
+                deposit,1,3,20.0
+                withdrawal,1,4,2.5
+            " =>
+            "
+                client,available,held,total,locked
+                1,27.5000,0.0000,27.5000,true
+            "
+        );
+    }
+
+    #[test]
+    async fn test_resolve_on_locked() {
+        let config = EngineConfig {
+            resolve_on_locked: true,
+            ..EngineConfig::default()
+        };
+        let engine = TransactionEngine::with_config(config.clone());
+
+        // Simulate an account that was frozen while a dispute was open
+        let mut account = Account::new(1);
+        account.held_balance = 25.0;
+        account.locked = true;
+        account.transactions.insert(
+            2.into(),
+            TransactionDetails {
+                tx_type: TransactionType::Deposit,
+                amount: 25.0,
+                held_amount: 25.0,
+                charged_back_amount: 0.0,
+                dispute_state: DisputeState::Disputed,
+            },
+        );
+
+        let record = |r#type| TransactionRecord {
+            r#type,
+            client_id: 1,
+            transaction_id: 2.into(),
+            amount: None,
+            ..Default::default()
+        };
+
+        // Chargebacks are still ignored
+        assert!(engine
+            .apply_transaction(&mut account, record(TransactionType::Chargeback))
+            .unwrap()
+            .is_some());
+        assert_eq!(account.held_balance, 25.0);
+
+        // The resolve releases the held funds, but the account stays locked
+        assert!(engine
+            .apply_transaction(&mut account, record(TransactionType::Resolve))
+            .unwrap()
+            .is_none());
+        assert_eq!(account.available_balance, 25.0);
+        assert_eq!(account.held_balance, 0.0);
+        assert!(account.locked);
+
+        // Open disputes on accounts locked by a chargeback can be resolved as well
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,1,
+                dispute,1,2,
+                chargeback,1,2,
+                resolve,1,1,
+                deposit,1,3,5.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,true
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_with_undisputed_chargeback() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                chargeback,1,2,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,35.0000,0.0000,35.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_total_balance_ceiling() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            total_balance_ceiling: Some(100.0),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,60.0
+                    deposit,1,2,40.0
+                    deposit,1,3,20.0
+                    deposit,1,4,20.0
+                    withdrawal,1,5,50.0
+                    deposit,1,6,60.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Only crossing the ceiling is reported, not staying above it
+        assert_eq!(
+            engine.warnings(),
+            vec![
+                EngineWarning::TotalBalanceCeilingExceeded {
+                    client_id: 1,
+                    tx_id: 3.into(),
+                    total: 120.0,
+                },
+                EngineWarning::TotalBalanceCeilingExceeded {
+                    client_id: 1,
+                    tx_id: 6.into(),
+                    total: 150.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    async fn test_skip_unparsable_records() {
+        let csv = "
+            type,client,tx,amount
+            deposit,1,1,10.0
+            deposit,x,2,5.0
+            withdrawal,1,3,2.5
+        ";
+
+        // Deserialize errors abort by default
+        let engine = TransactionEngine::new();
+        assert!(engine.process_records(records_from_csv(csv)).await.is_err());
+
+        let engine = TransactionEngine::with_config(EngineConfig {
+            skip_unparsable_records: true,
+            ..EngineConfig::default()
+        });
+        engine.process_records(records_from_csv(csv)).await.unwrap();
+
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 7.5);
+        assert_eq!(engine.stats().processed, 2);
+        let warnings = engine.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            warnings[0],
+            EngineWarning::UnparsableRecord { position: 2, .. }
+        ));
+
+        // All other entry points skip them as well
+        let config = EngineConfig {
+            skip_unparsable_records: true,
+            ..EngineConfig::default()
+        };
+        let engine = TransactionEngine::with_config(config.clone());
+        engine
+            .process_records_with_deadline(
+                records_from_csv(csv),
+                Instant::now() + Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 7.5);
+
+        let engine = TransactionEngine::with_config(config.clone());
+        engine
+            .process_raw_lines(
+                ["deposit,1,1,10.0", "deposit,x,2,5.0", "withdrawal,1,3,2.5"]
+                    .into_iter()
+                    .map(String::from),
+            )
+            .await
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 7.5);
+
+        let engine = TransactionEngine::with_config(config.clone());
+        engine
+            .process_batches(records_from_csv(csv), |_| Ok(()))
+            .await
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 7.5);
+
+        let engine = Arc::new(TransactionEngine::with_config(config.clone()));
+        engine
+            .process_sources(vec![records_from_csv(csv)])
+            .await
+            .unwrap();
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 7.5);
+
+        let engine = TransactionEngine::with_config(config);
+        let mut emitted = Vec::new();
+        engine
+            .process_records_streaming(records_from_csv(csv), |account| {
+                emitted.push(account.available_balance);
+                Ok(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(emitted, vec![7.5]);
+    }
+
+    #[test]
+    async fn test_tx_auto_lock_on_negative() {
+        assert_csv_snapshot!(
+            EngineConfig {
+                auto_lock_on_negative: true,
+                ..EngineConfig::default()
+            },
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,10.0
+                dispute,1,1,
+                deposit,1,3,50.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,-10.0000,10.0000,0.0000,true
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_negative_without_auto_lock() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,10.0
+                dispute,1,1,
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,-10.0000,10.0000,0.0000,false
+            "
+        )
+    }
+
+    #[test]
+    async fn test_tx_dispute_on_locked_account_ignored() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,5.0
+                dispute,1,1,
+                chargeback,1,1,
+                dispute,1,2,
+            " =>
+            "
+                client,available,held,total,locked
+                1,5.0000,0.0000,5.0000,true
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_dispute_on_locked_account_processed() {
+        let config = EngineConfig {
+            process_disputes_on_locked: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,5.0
+                dispute,1,1,
+                chargeback,1,1,
+                dispute,1,2,
+                deposit,1,3,20.0
+            " =>
+            "
+                client,available,held,total,locked
+                1,0.0000,5.0000,5.0000,true
+            "
+        );
+    }
+
+    #[test]
+    async fn test_require_opening_deposit() {
+        let config = EngineConfig {
+            require_opening_deposit: true,
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount
+                withdrawal,1,1,10.0
+                dispute,2,5,
+                deposit,3,2,10.0
+                withdrawal,3,3,4.0
+            " =>
+            "
+                client,available,held,total,locked
+                3,6.0000,0.0000,6.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_tx_deposit_with_locked_account() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,2,25.0
+                dispute,1,2,
+                chargeback,1,2,
+                deposit,1,3,50.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,true
+            "
+        )
+    }
+
+    #[test(flavor = "multi_thread")]
+    async fn test_multiple_deposits_withdrawals() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,2,2,10.0
+                deposit,1,3,10.0
+                deposit,5,4,10.0
+                deposit,4,5,10.0
+                withdrawal,1,6,20.0
+                deposit,6,7,10.0
+                deposit,3,8,20.0
+                dispute,2,2
+                deposit,3,9,5.0
+                deposit,2,10,10.0
+                resolve,2,2
+                dispute,3,8
+                deposit,7,11,15.0
+                dispute,7,11
+                chargeback,7,11
+                deposit,7,12,20.0
+            "
+            =>
+            "
+                client,available,held,total,locked
+                1,0.0000,0.0000,0.0000,false
+                2,20.0000,0.0000,20.0000,false
+                3,5.0000,20.0000,25.0000,false
+                4,10.0000,0.0000,10.0000,false
+                5,10.0000,0.0000,10.0000,false
+                6,10.0000,0.0000,10.0000,false
+                7,0.0000,0.0000,0.0000,true
+            "
+        )
+    }
+
+    #[test(flavor = "multi_thread", worker_threads = 8)]
+    async fn test_concurrent_account_creation() {
+        let engine = Arc::new(TransactionEngine::new());
+
+        // Hammer a single new client id from many tasks at once
+        let handles: Vec<_> = (0..256)
+            .map(|tx_id| {
+                let engine = Arc::clone(&engine);
+                tokio::spawn(async move {
+                    let record = TransactionRecord {
+                        r#type: TransactionType::Deposit,
+                        client_id: 1,
+                        transaction_id: tx_id.into(),
+                        amount: Some(1.0),
+                        ..Default::default()
+                    };
+                    engine
+                        .process_records(std::iter::once(Ok::<_, csv::Error>(record)))
+                        .await
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].available_balance, 256.0);
+    }
+
+    #[test]
+    async fn test_with_capacity() {
+        let input = "
+            type,client,tx,amount
+            deposit,1,1,10.0
+            deposit,2,2,20.0
+            withdrawal,1,3,5.0
+            dispute,2,2,
+            deposit,3,4,7.0
+        ";
+        let engine = TransactionEngine::new();
+        engine
+            .process_records(records_from_csv(input))
+            .await
+            .unwrap();
+        let presized = TransactionEngine::with_capacity(3);
+        presized
+            .process_records(records_from_csv(input))
+            .await
+            .unwrap();
+
+        assert_eq!(presized.accounts().unwrap(), engine.accounts().unwrap());
+    }
+
+    #[test]
+    async fn test_ledger_capacity() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            ledger_capacity: 64,
+            ..EngineConfig::default()
+        });
+        let deposits = |range: std::ops::Range<u32>| {
+            range.map(|tx_id| {
+                Ok::<_, csv::Error>(TransactionRecord {
+                    r#type: TransactionType::Deposit,
+                    client_id: 1,
+                    transaction_id: tx_id.into(),
+                    amount: Some(1.0),
+                    ..Default::default()
+                })
+            })
+        };
+
+        engine.process_records(deposits(0..1)).await.unwrap();
+        let capacity = engine.account(1).unwrap().unwrap().transactions.capacity();
+        assert!(capacity >= 64);
+
+        // Filling the pre-allocated ledger never grows it
+        engine.process_records(deposits(1..64)).await.unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.transactions.len(), 64);
+        assert_eq!(account.transactions.capacity(), capacity);
+    }
+
+    #[test]
+    async fn test_stats() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                withdrawal,1,2,20.0
+                dispute,1,3,
+                dispute,1,1,
+                chargeback,1,1,
+                deposit,1,4,5.0
+            ",
+        )
+        .await;
+        assert_eq!(
+            engine.stats(),
+            EngineStats {
+                processed: 6,
+                rejected: 3,
+                by_type: [
+                    (TransactionType::Deposit, 1),
+                    (TransactionType::Dispute, 1),
+                    (TransactionType::Chargeback, 1),
+                ]
+                .into_iter()
+                .collect(),
+                by_source: HashMap::new(),
+            }
+        );
+    }
+
+    #[test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_take_stats_concurrently() {
+        let engine = Arc::new(TransactionEngine::new());
+
+        // Process records from several producers while taking stats
+        let producers: Vec<_> = (0..4u16)
+            .map(|client_id| {
+                let engine = Arc::clone(&engine);
+                tokio::spawn(async move {
+                    for tx_id in 0..1000 {
+                        let record = TransactionRecord {
+                            r#type: TransactionType::Withdraw,
+                            client_id,
+                            transaction_id: tx_id.into(),
+                            amount: Some(1.0),
+                            ..Default::default()
+                        };
+                        engine
+                            .process_records(std::iter::once(Ok::<_, csv::Error>(record)))
+                            .await
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        // Take stats until all producers are done
+        let done = Arc::new(AtomicBool::new(false));
+        let taker = {
+            let engine = Arc::clone(&engine);
+            let done = Arc::clone(&done);
+            tokio::spawn(async move {
+                let mut total = EngineStats::default();
+                while !done.load(Ordering::SeqCst) {
+                    let stats = engine.take_stats();
+                    total.processed += stats.processed;
+                    total.rejected += stats.rejected;
+                    tokio::task::yield_now().await;
+                }
+                total
+            })
+        };
+        for producer in producers {
+            producer.await.unwrap();
+        }
+        done.store(true, Ordering::SeqCst);
+        let mut total = taker.await.unwrap();
+
+        // Collect whatever was counted after the last take
+        let stats = engine.take_stats();
+        total.processed += stats.processed;
+        total.rejected += stats.rejected;
+        assert_eq!(
+            total,
+            EngineStats {
+                processed: 4000,
+                rejected: 4000,
+                ..EngineStats::default()
+            }
+        );
+        assert_eq!(engine.take_stats(), EngineStats::default());
+    }
+
+    #[test]
+    async fn test_allowed_clients() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            allowed_clients: Some([1, 3].into_iter().collect()),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,2,2,20.0
+                    deposit,3,3,30.0
+                    withdrawal,2,4,5.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        let client_ids: Vec<u16> = engine
+            .finalize()
+            .unwrap()
+            .iter()
+            .map(|account| account.client_id)
+            .collect();
+        assert_eq!(client_ids, vec![1, 3]);
+    }
+
+    #[test]
+    async fn test_tx_id_collision() {
+        // Reusing an id of another client can't collide, since ledgers are per client
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                deposit,1,1,20.0
+                withdrawal,1,1,5.0
+                deposit,2,1,30.0
+            " =>
+            "
+                client,available,held,total,locked
+                1,10.0000,0.0000,10.0000,false
+                2,30.0000,0.0000,30.0000,false
+            "
+        );
+    }
+
+    #[cfg(feature = "wide-tx-ids")]
+    #[test]
+    async fn test_wide_tx_ids() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,4294967296,10.0
+                deposit,1,4294967297,20.0
+                dispute,1,4294967296,
+            ",
+        )
+        .await;
+
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.held_balance, 10.0);
+        assert!(account.transactions[&TxId::new(1 << 32).unwrap()].is_disputed());
+        assert!(!account.transactions[&TxId::new((1 << 32) + 1).unwrap()].is_disputed());
+    }
+
+    #[test]
+    async fn test_ingest_rounding() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            ingest_rounding: Some(2),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.123456
+                    deposit,2,2,2.345678
+                ",
+            ))
+            .await
+            .unwrap();
+
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 10.12);
+        assert_eq!(account.transactions[&TxId::from(1)].amount, 10.12);
+        let account = engine.account(2).unwrap().unwrap();
+        assert_eq!(account.available_balance, 2.35);
+        assert_eq!(account.transactions[&TxId::from(2)].amount, 2.35);
+
+        // Amounts rounding to zero are invalid
+        let engine = TransactionEngine::with_config(EngineConfig {
+            ingest_rounding: Some(2),
+            ..EngineConfig::default()
+        });
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,0.001
+                ",
+            ))
+            .await;
+        assert!(result.is_err());
+        assert!(engine.account(1).unwrap().is_none());
+    }
+
+    #[test]
+    async fn test_dispute_for_unknown_client() {
+        let engine = TransactionEngine::new();
+        let mut outcomes = Vec::new();
+        for record in records_from_csv(
+            "
+                type,client,tx,amount
+                deposit,1,1,10.0
+                dispute,2,1,
+                resolve,3,1,
+                chargeback,4,1,
+                reversal,5,1,
+                withdrawal,6,2,5.0
+            ",
+        ) {
+            outcomes.push(engine.process_record(record.unwrap()).await.unwrap());
+        }
+
+        assert_eq!(outcomes[1..5], [Some(RejectReason::UnknownTransaction); 4]);
+        let client_ids: Vec<u16> = engine
+            .finalize()
+            .unwrap()
+            .iter()
+            .map(|account| account.client_id)
+            .collect();
+        assert_eq!(client_ids, vec![1, 6]);
+    }
+
+    #[test]
+    async fn test_allowed_sources() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            allowed_sources: Some(["bank".to_string()].into_iter().collect()),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount,source
+                    deposit,1,1,10.0,bank
+                    deposit,1,2,20.0,wallet
+                    withdrawal,1,3,5.0,bank
+                    deposit,2,4,30.0,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 5.0);
+        assert!(engine.account(2).unwrap().is_none());
+        let stats = engine.stats();
+        assert_eq!(stats.rejected, 2);
+        assert_eq!(
+            stats.by_source,
+            HashMap::from([("bank".to_string(), 2), ("wallet".to_string(), 1)])
+        );
+    }
+
+    #[test]
+    async fn test_reserved_client_ids() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            reserved_client_ids: [0].into_iter().collect(),
+            ..EngineConfig::default()
+        });
+        let mut outcomes = Vec::new();
+        for record in records_from_csv(
+            "
+                type,client,tx,amount
+                deposit,0,1,10.0
+                deposit,1,2,20.0
+                withdrawal,0,3,5.0
+            ",
+        ) {
+            outcomes.push(engine.process_record(record.unwrap()).await.unwrap());
+        }
+
+        assert_eq!(
+            outcomes,
+            vec![
+                Some(RejectReason::ReservedClient),
+                None,
+                Some(RejectReason::ReservedClient)
+            ]
+        );
+        let client_ids: Vec<u16> = engine
+            .finalize()
+            .unwrap()
+            .iter()
+            .map(|account| account.client_id)
+            .collect();
+        assert_eq!(client_ids, vec![1]);
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    async fn test_unread_results_warning() {
+        let engine = TransactionEngine::new();
+        assert!(engine.unread_results_warning().is_none());
+
+        // Process records, but forget to read the accounts
+        let records = vec![Ok::<_, csv::Error>(TransactionRecord {
+            r#type: TransactionType::Deposit,
+            client_id: 1,
+            transaction_id: 1.into(),
+            amount: Some(1.0),
+            ..Default::default()
+        })];
+        engine.process_records(records.into_iter()).await.unwrap();
+        assert_eq!(
+            engine.unread_results_warning().as_deref(),
+            Some("TransactionEngine dropped after processing 1 records, but the accounts were never read.")
+        );
+
+        // Reading the accounts silences the warning
+        engine.accounts().unwrap();
+        assert!(engine.unread_results_warning().is_none());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    async fn test_unread_results_warning_on_drop() {
+        let take_warnings = || super::UNREAD_RESULTS_WARNINGS.with(|warnings| warnings.take());
+        take_warnings();
+
+        // Dropping an engine whose results were never read warns
+        let engine = engine_from_csv("type,client,tx,amount\ndeposit,1,1,1.0").await;
+        drop(engine);
+        assert_eq!(
+            take_warnings(),
+            vec!["TransactionEngine dropped after processing 1 records, but the accounts were never read."]
+        );
+
+        // Any result accessor counts as reading the results
+        let accessors: Vec<fn(&TransactionEngine)> = vec![
+            |engine| drop(engine.len()),
+            |engine| drop(engine.is_empty()),
+            |engine| drop(engine.account(1)),
+            |engine| drop(engine.clients_above(0.0)),
+            |engine| drop(engine.held_breakdown()),
+            |engine| drop(engine.deltas_since(&HashMap::new())),
+            |engine| drop(engine.aggregate_balances()),
+            |engine| drop(engine.state_hash()),
+            |engine| drop(engine.export_ledger(std::io::sink())),
+        ];
+        for read in accessors {
+            let engine = engine_from_csv("type,client,tx,amount\ndeposit,1,1,1.0").await;
+            read(&engine);
+            drop(engine);
+            assert!(take_warnings().is_empty());
+        }
+    }
+
+    #[test]
+    async fn test_transaction_status() {
+        assert_csv_snapshot!(
+            "
+                type,client,tx,amount,status
+                deposit,1,1,10.0,posted
+                deposit,1,2,20.0,pending
+                deposit,1,3,30.0,cancelled
+                deposit,1,4,40.0,
+                withdrawal,1,5,5.0,pending
+            " =>
+            "
+                client,available,held,total,locked
+                1,50.0000,0.0000,50.0000,false
+            "
+        );
+
+        let config = EngineConfig {
+            applied_statuses: Some(
+                [TransactionStatus::Posted, TransactionStatus::Pending]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..EngineConfig::default()
+        };
+        assert_csv_snapshot!(
+            config,
+            "
+                type,client,tx,amount,status
+                deposit,1,1,10.0,posted
+                deposit,1,2,20.0,pending
+                deposit,1,3,30.0,cancelled
+            " =>
+            "
+                client,available,held,total,locked
+                1,30.0000,0.0000,30.0000,false
+            "
+        );
+    }
+
+    #[test]
+    async fn test_allowed_clients_counts_filtered_records() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            allowed_clients: Some([1].into_iter().collect()),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,2,2,20.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        assert!(engine.account(2).unwrap().is_none());
+        assert_eq!(
+            engine.stats(),
+            EngineStats {
+                processed: 2,
+                rejected: 1,
+                by_type: [(TransactionType::Deposit, 1)].into_iter().collect(),
+                by_source: HashMap::new(),
+            }
+        );
+    }
+
+    #[test]
+    async fn test_process_csv_bytes() {
+        let input = "type, client, tx, amount
+deposit, 1, 1, 10.0
+deposit, 2, 2, 5.0
+withdrawal, 1, 3, 2.5
+dispute, 2, 2
+";
+        let engine = TransactionEngine::new();
+        engine.process_csv_bytes(input.as_bytes()).await.unwrap();
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[0].available_balance, 7.5);
+        assert_eq!(accounts[1].held_balance, 5.0);
+    }
+
+    #[test]
+    async fn test_process_raw_lines() {
+        let engine = TransactionEngine::new();
+        let lines = [
+            "deposit,1,1,10.0",
+            " withdrawal, 1, 2, 2.5 ",
+            "",
+            "deposit,2,3,5.0",
+            "dispute,2,3",
+            "deposit,3,4,\"1.0\"",
+            "\"deposit\",3,5,\" 2.5 \"",
+        ];
+        engine
+            .process_raw_lines(lines.iter().map(|line| line.to_string()))
+            .await
+            .unwrap();
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0].available_balance, 7.5);
+        assert_eq!(accounts[1].available_balance, 0.0);
+        assert_eq!(accounts[1].held_balance, 5.0);
+
+        // Quoted fields are read like in the main CSV reader
+        assert_eq!(accounts[2].available_balance, 3.5);
+
+        // Malformed lines are reported with their line number
+        let err = engine
+            .process_raw_lines(std::iter::once("deposit,1,x,1.0".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Unable to parse line 1.");
+    }
+
+    #[test]
+    async fn test_rollback() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 10,
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,1,2,20.0
+                    deposit,1,3,30.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(engine.rollback(2).unwrap(), 2);
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 10.0);
+        assert_eq!(account.transactions.len(), 1);
+
+        // Only the remaining journaled record can be rolled back
+        assert_eq!(engine.rollback(5).unwrap(), 1);
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 0.0);
+    }
+
+    #[test]
+    async fn test_rollback_restores_evicted_entries() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 10,
+            max_ledger_entries: Some(2),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount,client_name,currency
+                    deposit,1,1,10.0,Alice,EUR
+                    deposit,1,2,20.0,,
+                    deposit,1,3,30.0,,
+                ",
+            ))
+            .await
+            .unwrap();
+        assert!(!engine
+            .account(1)
+            .unwrap()
+            .unwrap()
+            .transactions
+            .contains_key(&TxId::from(1)));
+
+        // Rolling back the third deposit brings back the entry it evicted
+        assert_eq!(engine.rollback(1).unwrap(), 1);
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    dispute,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.held_balance, 10.0);
+        assert_eq!(account.ledger_order, vec![TxId::from(1), TxId::from(2)]);
+
+        // Rolling back everything also forgets the metadata of the first deposit
+        assert_eq!(engine.rollback(5).unwrap(), 3);
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.name, None);
+        assert_eq!(account.currency, None);
+        assert!(account.transactions.is_empty());
+    }
+
+    #[test]
+    async fn test_rollback_skips_rejected_records() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 10,
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,1,2,20.0
+                    withdrawal,1,3,50.0
+                    dispute,1,9,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // The rejected withdrawal and dispute aren't journaled, so the last deposit is rolled back
+        assert_eq!(engine.rollback(1).unwrap(), 1);
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 10.0);
+        assert_eq!(engine.rollback(5).unwrap(), 1);
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 0.0);
+    }
+
+    #[test]
+    async fn test_rollback_dispute_latest_deposit() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 2,
+            dispute_latest_deposit: true,
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    dispute,1,0,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Rolling back the tx-less dispute restores the deposit it was pointed to
+        assert_eq!(engine.rollback(1).unwrap(), 1);
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 10.0);
+        assert_eq!(account.held_balance, 0.0);
+        assert_eq!(
+            account.transactions[&TxId::from(1)].dispute_state,
+            DisputeState::None
+        );
+        assert_eq!(account.transactions[&TxId::from(1)].held_amount, 0.0);
+
+        // A resolve can't release funds that are no longer held
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    resolve,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 10.0);
+        assert_eq!(account.held_balance, 0.0);
+    }
+
+    #[test]
+    async fn test_rollback_chargeback() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            journal_size: 2,
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    dispute,1,1,
+                    chargeback,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Rolling back the chargeback restores the open dispute
+        assert_eq!(engine.rollback(1).unwrap(), 1);
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.held_balance, 10.0);
+        assert!(!account.locked);
+        assert!(account.transactions[&TxId::from(1)].is_disputed());
+
+        // The deposit fell out of the journal
+        assert_eq!(engine.rollback(2).unwrap(), 1);
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 10.0);
+        assert_eq!(account.held_balance, 0.0);
+    }
+
+    #[test]
+    async fn test_event_sink() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let engine = {
+            let events = Arc::clone(&events);
+            TransactionEngine::new()
+                .with_event_sink(move |event| events.lock().unwrap().push(event))
+        };
+
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    withdrawal,1,2,3.0
+                    withdrawal,1,3,50.0
+                    dispute,1,1,
+                    resolve,1,1,4.0
+                    resolve,1,1,
+                    dispute,1,1,
+                    chargeback,1,1,
+                    deposit,1,4,1.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Rejected records don't emit events
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                EngineEvent::Deposited {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 10.0
+                },
+                EngineEvent::Withdrawn {
+                    client_id: 1,
+                    tx_id: 2.into(),
+                    amount: 3.0
+                },
+                EngineEvent::DisputeOpened {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 10.0
+                },
+                EngineEvent::Resolved {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 4.0
+                },
+                EngineEvent::Resolved {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 6.0
+                },
+                EngineEvent::DisputeOpened {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 10.0
+                },
+                EngineEvent::ChargedBack {
+                    client_id: 1,
+                    tx_id: 1.into(),
+                    amount: 10.0
+                },
+                EngineEvent::AccountLocked {
+                    client_id: 1,
+                    tx_id: 1.into()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    async fn test_dedup_predicate() {
+        let engine = TransactionEngine::new().with_dedup_predicate(|tx_id| tx_id.get() % 2 != 0);
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,1,2,20.0
+                    deposit,1,3,30.0
+                    withdrawal,1,4,5.0
+                    dispute,1,1,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Even tx ids are skipped, disputes aren't affected
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 30.0);
+        assert_eq!(account.held_balance, 10.0);
+        assert_eq!(engine.stats().rejected, 2);
+    }
+
+    #[test]
+    async fn test_tenant_isolation() {
+        let engine = TransactionEngine::new();
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount,tenant
+                    deposit,1,1,10.0,acme
+                    deposit,1,1,20.0,globex
+                    deposit,1,2,5.0,globex
+                    dispute,1,2,,acme
+                    dispute,1,1,,globex
+                    deposit,1,3,1.0,
+                ",
+            ))
+            .await
+            .unwrap();
+
+        // Disputes only match transactions of the same tenant
+        let acme = engine.tenant_accounts(Some("acme")).unwrap();
+        assert_eq!(acme.len(), 1);
+        assert_eq!(acme[0].available_balance, 10.0);
+        assert_eq!(acme[0].held_balance, 0.0);
+
+        let globex = engine.tenant_accounts(Some("globex")).unwrap();
+        assert_eq!(globex.len(), 1);
+        assert_eq!(globex[0].available_balance, 5.0);
+        assert_eq!(globex[0].held_balance, 20.0);
+
+        // Records without a tenant belong to the default tenant
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 1.0);
+        assert_eq!(engine.accounts().unwrap().len(), 3);
+
+        // FFI accounts can't be told apart by tenant, so they're returned per tenant
+        let cents: Vec<_> = engine
+            .accounts_ffi(Some("globex"))
+            .unwrap()
+            .iter()
+            .map(|account| (account.client_id, account.available_cents))
+            .collect();
+        assert_eq!(cents, vec![(1, 500)]);
+        assert_eq!(engine.accounts_ffi(None).unwrap()[0].available_cents, 100);
+    }
+
+    #[test(flavor = "multi_thread")]
+    async fn test_process_disjoint_sources() {
+        let engine = Arc::new(TransactionEngine::new());
+        let shards = vec![
+            records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    withdrawal,1,2,4.0
+                    dispute,1,1,
+                ",
+            ),
+            records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,2,3,20.0
+                    deposit,3,4,5.0
+                    withdrawal,2,5,2.5
+                ",
+            ),
+        ];
+        engine.process_sources(shards).await.unwrap();
+
+        let accounts = engine.accounts().unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0].available_balance, -4.0);
+        assert_eq!(accounts[0].held_balance, 10.0);
+        assert_eq!(accounts[1].available_balance, 17.5);
+        assert_eq!(accounts[2].available_balance, 5.0);
+    }
+
+    #[test(flavor = "multi_thread")]
+    async fn test_process_sources_bookkeeping() {
+        let engine = Arc::new(TransactionEngine::with_config(EngineConfig {
+            auto_stream_threshold: Some(2),
+            ..EngineConfig::default()
+        }));
+        let shards = vec![records_from_csv(
+            "
+                type,client,tx,amount,source
+                deposit,1,1,10.0,a
+                deposit,1,2,5.0,a
+                withdrawal,1,3,2.5,b
+            ",
+        )];
+        engine.process_sources(shards).await.unwrap();
+
+        // Records are counted per source and the threshold switches to streaming
+        let stats = engine.stats();
+        assert_eq!(stats.processed, 3);
+        assert_eq!(stats.by_source["a"], 2);
+        assert_eq!(stats.by_source["b"], 1);
+        assert!(engine.is_streaming());
+    }
+
+    #[test(flavor = "multi_thread")]
+    async fn test_process_overlapping_sources() {
+        let engine = Arc::new(TransactionEngine::new());
+        let shards = vec![
+            records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                ",
+            ),
+            records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,2,2,20.0
+                    deposit,1,3,5.0
+                ",
+            ),
+        ];
+        assert!(engine.process_sources(shards).await.is_err());
+    }
+
+    #[test(flavor = "multi_thread")]
+    async fn test_process_sources_stops_on_error() {
+        let engine = Arc::new(TransactionEngine::new());
+        let long_shard: String = std::iter::once("type,client,tx,amount".to_string())
+            .chain((0..20_000).map(|tx| format!("deposit,2,{},1.0", tx)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let shards = vec![
+            records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,x,10.0
+                ",
+            ),
+            records_from_csv(&long_shard),
+        ];
+        assert!(engine.process_sources(shards).await.is_err());
+
+        // The other source doesn't keep processing after the error was returned
+        let processed = engine.stats().processed;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert_eq!(engine.stats().processed, processed);
+    }
+
+    #[test]
+    async fn test_account_metadata() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount,client_name,currency
+                deposit,1,1,10.0,Alice,USD
+                deposit,1,2,5.0,Bob,USD
+                deposit,2,3,1.0,,
+            ",
+        )
+        .await;
+
+        let config = OutputConfig {
+            with_metadata: true,
+            ..OutputConfig::default()
+        };
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        writer.write_record(config.headers()).unwrap();
+        for account in engine.accounts().unwrap() {
+            writer.serialize(account.output(&config)).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "client,available,held,total,locked,client_name,currency\n\
+             1,15.0000,0.0000,15.0000,false,Alice,USD\n\
+             2,1.0000,0.0000,1.0000,false,,\n"
+        );
+    }
+
+    #[test]
+    async fn test_currency_mismatch() {
+        let engine = engine_from_csv(
+            "
+                type,client,tx,amount,client_name,currency
+                deposit,1,1,10.0,Alice,USD
+                withdrawal,1,2,4.0,Alice,EUR
+                withdrawal,1,3,1.0,,
+                withdrawal,1,4,2.0,Alice,USD
+            ",
+        )
+        .await;
+
+        // Only the EUR withdrawal is rejected, records without a currency aren't checked
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.currency.as_deref(), Some("USD"));
+        assert_eq!(account.available_balance, 7.0);
+        assert_eq!(engine.stats().rejected, 1);
+
+        // Rejected records don't pin the currency or name of the account
+        let engine = engine_from_csv(
             "
+                type,client,tx,amount,client_name,currency
+                withdrawal,1,1,5.0,Mallory,EUR
+                deposit,1,2,10.0,Alice,USD
+            ",
         )
+        .await;
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.currency.as_deref(), Some("USD"));
+        assert_eq!(account.name.as_deref(), Some("Alice"));
+        assert_eq!(account.available_balance, 10.0);
     }
 
     #[test]
-    async fn test_tx_deposits_withdrawals_positive_balance() {
-        assert_csv_snapshot!(
+    async fn test_with_account() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                withdrawal,1,3,15.0
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,20.0,0.0,20.0,false
-            "
+                deposit,1,2,5.0
+                dispute,1,2,
+            ",
         )
+        .await;
+
+        assert_eq!(
+            engine.with_account(1, Account::total_balance).unwrap(),
+            Some(15.0)
+        );
+        assert_eq!(
+            engine.with_account(2, Account::total_balance).unwrap(),
+            None
+        );
     }
 
     #[test]
-    async fn test_tx_deposits_withdrawals_zero_balance() {
-        assert_csv_snapshot!(
+    async fn test_check_zero_sum() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            assert_zero_sum: Some(ZeroSumCheck {
+                expected: 0.0,
+                tolerance: 0.0001,
+            }),
+            ..EngineConfig::default()
+        });
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,2,2,5.0
+                    dispute,2,2,
+                    withdrawal,1,3,4.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            engine.aggregate_balances().unwrap(),
+            AggregateBalances {
+                total_available: 6.0,
+                total_held: 5.0,
+            }
+        );
+        assert!(!engine.check_zero_sum().unwrap());
+        assert_eq!(
+            engine.warnings(),
+            vec![EngineWarning::ZeroSumViolated {
+                expected: 0.0,
+                actual: 11.0,
+            }]
+        );
+
+        // Nothing is checked without the setting
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                withdrawal,1,3,35.0
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,0.0,0.0,0.0,false
-            "
+            ",
         )
+        .await;
+        assert!(engine.check_zero_sum().unwrap());
+        assert!(engine.warnings().is_empty());
     }
 
     #[test]
-    async fn test_tx_deposits_withdrawals_negative_balance() {
-        assert_csv_snapshot!(
+    async fn test_check_zero_sum_many_accounts() {
+        let engine = TransactionEngine::with_config(EngineConfig {
+            assert_zero_sum: Some(ZeroSumCheck {
+                expected: 6000.0,
+                tolerance: 0.01,
+            }),
+            ..EngineConfig::default()
+        });
+        let csv: String = std::iter::once("type,client,tx,amount".to_string())
+            .chain((0..60_000).map(|tx| format!("deposit,{},{},0.1", tx, tx)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        engine
+            .process_records(records_from_csv(&csv))
+            .await
+            .unwrap();
+
+        // Summing in f32 would drift far beyond the tolerance
+        assert!(engine.check_zero_sum().unwrap());
+        assert!(engine.warnings().is_empty());
+    }
+
+    #[test]
+    async fn test_account_activity() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                withdrawal,1,3,40.0
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,35.0,0.0,35.0,false
-            "
+                deposit,1,2,5.0
+                withdrawal,1,3,4.0
+                withdrawal,1,4,100.0
+                dispute,1,1,
+                chargeback,1,1,
+                deposit,1,5,1.0
+                deposit,2,6,2.5
+            ",
         )
+        .await;
+
+        // Rejected transfers aren't counted, disputes don't change the counters
+        assert_eq!(
+            engine.with_account(1, Account::activity).unwrap(),
+            Some(AccountActivity {
+                deposit_count: 2,
+                withdrawal_count: 1,
+                total_deposited: 15.0,
+                total_withdrawn: 4.0,
+            })
+        );
+
+        let config = OutputConfig {
+            with_activity: true,
+            ..OutputConfig::default()
+        };
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        writer.write_record(config.headers()).unwrap();
+        for account in engine.accounts().unwrap() {
+            writer.serialize(account.output(&config)).unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(writer.into_inner().unwrap()).unwrap(),
+            "client,available,held,total,locked,deposit_count,withdrawal_count,total_deposited,total_withdrawn\n\
+             1,1.0000,0.0000,1.0000,true,2,1,15.0000,4.0000\n\
+             2,2.5000,0.0000,2.5000,false,1,0,2.5000,0.0000\n"
+        );
     }
 
     #[test]
-    async fn test_tx_dispute() {
-        assert_csv_snapshot!(
+    async fn test_clients_above() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                dispute,1,2,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,10.0,25.0,35.0,false
-            "
+                deposit,2,2,50.0
+                deposit,3,3,30.0
+                deposit,4,4,20.0
+                dispute,3,3,
+            ",
         )
+        .await;
+
+        assert_eq!(
+            engine.clients_above(20.0).unwrap(),
+            vec![(2, 50.0), (3, 30.0)]
+        );
     }
 
     #[test]
-    async fn test_tx_with_invalid_dispute() {
-        assert_csv_snapshot!(
+    async fn test_held_breakdown() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
+                deposit,1,2,20.0
+                deposit,1,3,30.0
+                deposit,2,4,40.0
                 dispute,1,3,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,35.0,0.0,35.0,false
-            "
+                dispute,1,1,
+                dispute,2,4,
+                resolve,2,4,
+            ",
         )
+        .await;
+
+        assert_eq!(
+            engine.held_breakdown().unwrap(),
+            HashMap::from([(1, vec![(TxId::from(1), 10.0), (TxId::from(3), 30.0)])])
+        );
     }
 
     #[test]
-    async fn test_tx_dispute_with_resolution() {
-        assert_csv_snapshot!(
+    async fn test_len() {
+        let engine = TransactionEngine::new();
+        assert!(engine.is_empty().unwrap());
+
+        let mut lens = Vec::new();
+        for record in records_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                dispute,1,2,
-                deposit,1,3,10.0
-                resolve,1,2,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,45.0,0.0,45.0,false
-            "
-        )
+                deposit,2,2,20.0
+                withdrawal,1,3,5.0
+                deposit,3,4,30.0
+            ",
+        ) {
+            engine.process_record(record.unwrap()).await.unwrap();
+            lens.push(engine.len().unwrap());
+        }
+
+        assert_eq!(lens, vec![1, 2, 2, 3]);
+        assert!(!engine.is_empty().unwrap());
     }
 
     #[test]
-    async fn test_tx_dispute_with_invalid_resolution() {
-        assert_csv_snapshot!(
+    async fn test_deltas_since() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                dispute,1,2,
-                deposit,1,3,10.0
-                resolve,1,4,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,20.0,25.0,45.0,false
-            "
+                deposit,2,2,20.0
+                withdrawal,2,3,5.0
+            ",
         )
+        .await;
+        let baseline = HashMap::from([(1, 4.0), (3, 2.5)]);
+
+        assert_eq!(
+            engine.deltas_since(&baseline).unwrap(),
+            HashMap::from([(1, 6.0), (2, 15.0), (3, -2.5)])
+        );
     }
 
     #[test]
-    async fn test_tx_undisputed_resolution() {
-        assert_csv_snapshot!(
+    async fn test_state_hash() {
+        let csv = "
+            type,client,tx,amount
+            deposit,1,1,10.0
+            deposit,2,2,20.0
+            withdrawal,1,3,2.5
+            dispute,2,2,
+        ";
+        let first = engine_from_csv(csv).await.state_hash().unwrap();
+        let second = engine_from_csv(csv).await.state_hash().unwrap();
+        assert_eq!(first, second);
+
+        // Different balances result in a different hash
+        let other = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                resolve,1,2,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,35.0,0.0,35.0,false
-            "
+                deposit,2,2,20.0
+                withdrawal,1,3,2.5
+            ",
         )
+        .await
+        .state_hash()
+        .unwrap();
+        assert_ne!(first, other);
     }
 
     #[test]
-    async fn test_tx_dispute_with_chargeback() {
-        assert_csv_snapshot!(
+    async fn test_process_records_with_deadline() {
+        let engine = TransactionEngine::new();
+        let deadline = Instant::now() + Duration::from_millis(50);
+
+        // The fourth record is slow to produce, the deadline passes while reading it
+        let records = (1..=10).map(|tx_id| {
+            if tx_id == 4 {
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            Ok::<_, csv::Error>(TransactionRecord {
+                r#type: TransactionType::Deposit,
+                client_id: 1,
+                transaction_id: tx_id.into(),
+                amount: Some(1.0),
+                ..Default::default()
+            })
+        });
+        let err = engine
+            .process_records_with_deadline(records, deadline)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<EngineError>(),
+            Some(&EngineError::Timeout { processed: 4 })
+        );
+
+        // Records processed before the deadline are kept
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 4.0);
+    }
+
+    #[test]
+    async fn test_lock_where() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                dispute,1,2,
-                chargeback,1,2,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,10.0,0.0,10.0,true
-            "
+                withdrawal,1,2,8.0
+                dispute,1,1,
+                chargeback,1,1,
+                deposit,2,3,5.0
+                deposit,3,4,1.0
+            ",
         )
+        .await;
+
+        // Client 1 is locked by the chargeback with a negative total
+        assert_eq!(engine.unlock_where(|_| true).unwrap(), 1);
+        assert_eq!(
+            engine
+                .lock_where(|account| account.total_balance() < 0.0)
+                .unwrap(),
+            1
+        );
+        assert!(engine.account(1).unwrap().unwrap().locked);
+        assert!(!engine.account(2).unwrap().unwrap().locked);
+
+        // Accounts that are already locked aren't counted
+        assert_eq!(
+            engine.lock_where(|account| account.client_id != 3).unwrap(),
+            1
+        );
+        assert!(engine.account(2).unwrap().unwrap().locked);
+        assert!(!engine.account(3).unwrap().unwrap().locked);
+    }
+
+    // Helper function to build a record iterator from a string.
+    fn records_from_csv(
+        csv: &str,
+    ) -> impl Iterator<Item = Result<TransactionRecord, csv::Error>> + Send + Sync {
+        let input: String = csv.split_whitespace().map(|s| format!("{}\n", s)).collect();
+        csv::ReaderBuilder::new()
+            .delimiter(b',')
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(std::io::Cursor::new(input))
+            .into_deserialize()
     }
 
     #[test]
-    async fn test_tx_with_undisputed_chargeback() {
-        assert_csv_snapshot!(
+    async fn test_streaming_grouped_input() {
+        let engine = TransactionEngine::new();
+        let mut emitted = Vec::new();
+        engine
+            .process_records_streaming(
+                records_from_csv(
+                    "
+                        type,client,tx,amount
+                        deposit,1,1,10.0
+                        withdrawal,1,2,4.0
+                        deposit,2,3,20.0
+                        deposit,3,4,5.0
+                        withdrawal,3,5,10.0
+                    ",
+                ),
+                |account| {
+                    emitted.push((account.client_id, account.available_balance));
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(emitted, vec![(1, 6.0), (2, 20.0), (3, 5.0)]);
+        assert!(engine.accounts().unwrap().is_empty());
+    }
+
+    #[test]
+    async fn test_process_and_stream_output() {
+        // Buffer shared with the writer task
+        #[derive(Clone, Default)]
+        struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+        impl std::io::Write for SharedBuffer {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // More clients than fit into the channel, so processing has to wait for the writer
+        let engine = TransactionEngine::new();
+        let output = SharedBuffer::default();
+        let input: String = (1..=3000)
+            .map(|client| {
+                format!(
+                    "deposit,{},{},10.0\nwithdrawal,{},{},4.0\n",
+                    client,
+                    client * 2,
+                    client,
+                    client * 2 + 1
+                )
+            })
+            .collect();
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(input.as_bytes());
+        engine
+            .process_and_stream_output(
+                reader.into_deserialize(),
+                &OutputConfig::default(),
+                output.clone(),
+            )
+            .await
+            .unwrap();
+
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 3001);
+        assert_eq!(lines[0], "client,available,held,total,locked");
+        for (client, line) in (1..=3000).zip(&lines[1..]) {
+            assert_eq!(*line, format!("{},6.0000,0.0000,6.0000,false", client));
+        }
+    }
+
+    #[test]
+    async fn test_streaming_rejects_ungrouped_input() {
+        let engine = TransactionEngine::new();
+        let result = engine
+            .process_records_streaming(
+                records_from_csv(
+                    "
+                        type,client,tx,amount
+                        deposit,1,1,10.0
+                        deposit,2,2,20.0
+                        deposit,1,3,5.0
+                    ",
+                ),
+                |_| Ok(()),
+            )
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn test_streaming_rejects_disputes() {
+        let engine = TransactionEngine::new();
+        let result = engine
+            .process_records_streaming(
+                records_from_csv(
+                    "
+                        type,client,tx,amount
+                        deposit,1,1,10.0
+                        dispute,1,1,
+                    ",
+                ),
+                |_| Ok(()),
+            )
+            .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    async fn test_progress_callback() {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let engine = {
+            let reported = Arc::clone(&reported);
+            TransactionEngine::new().with_progress_callback(3, move |count| {
+                reported.lock().unwrap().push(count);
+            })
+        };
+
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,1.0
+                    deposit,1,2,1.0
+                    deposit,1,3,1.0
+                    deposit,1,4,1.0
+                    deposit,1,5,1.0
+                    deposit,1,6,1.0
+                    deposit,1,7,1.0
+                ",
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(*reported.lock().unwrap(), vec![3, 6]);
+        assert_eq!(engine.accounts().unwrap()[0].available_balance, 7.0);
+    }
+
+    #[test]
+    async fn test_finalize() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
-                deposit,1,1,10.0
-                deposit,1,2,25.0
-                chargeback,1,2,
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,35.0,0.0,35.0,false
+                deposit,3,1,5.0
+                deposit,1,2,10.0
+                deposit,2,3,2.5
+                dispute,2,3,
+            ",
+        )
+        .await;
+
+        let accounts = engine.finalize().unwrap();
+        let client_ids: Vec<u16> = accounts.iter().map(|account| account.client_id).collect();
+        assert_eq!(client_ids, vec![1, 2, 3]);
+        assert_eq!(accounts[1].held_balance, 2.5);
+        assert!(accounts
+            .iter()
+            .all(|account| account.transactions.is_empty()));
+    }
+
+    #[test]
+    async fn test_account_lookup() {
+        let engine = engine_from_csv(
             "
+                type,client,tx,amount
+                deposit,5,1,10.0
+                deposit,5,2,5.0
+                dispute,5,2,
+            ",
         )
+        .await;
+
+        let mut expected = Account::new(5);
+        expected.available_balance = 10.0;
+        expected.held_balance = 5.0;
+        expected.transactions.insert(
+            1.into(),
+            TransactionDetails::new(TransactionType::Deposit, 10.0),
+        );
+        expected.transactions.insert(
+            2.into(),
+            TransactionDetails {
+                tx_type: TransactionType::Deposit,
+                amount: 5.0,
+                held_amount: 5.0,
+                charged_back_amount: 0.0,
+                dispute_state: DisputeState::Disputed,
+            },
+        );
+        expected.activity = AccountActivity {
+            deposit_count: 2,
+            total_deposited: 15.0,
+            ..AccountActivity::default()
+        };
+
+        assert_eq!(engine.account(5).unwrap(), Some(expected));
+        assert_eq!(engine.account(6).unwrap(), None);
     }
 
     #[test]
-    async fn test_tx_deposit_with_locked_account() {
-        assert_csv_snapshot!(
+    async fn test_process_batches() {
+        let engine = TransactionEngine::new();
+        let mut checkpoints = Vec::new();
+        let mut checkpoint_accounts = Vec::new();
+
+        let err = engine
+            .process_batches(
+                records_from_csv(
+                    "
+                        type,client,tx,amount,batch
+                        deposit,1,1,10.0,a
+                        deposit,2,2,20.0,a
+                        withdrawal,1,3,5.0,b
+                        deposit,3,4,7.0,b
+                        deposit,2,5,,b
+                    ",
+                ),
+                |batch| {
+                    checkpoints.push(batch.map(String::from));
+                    checkpoint_accounts = engine.accounts()?;
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap_err();
+
+        // The second batch failed, so only the first one was checkpointed
+        assert_eq!(
+            err.to_string(),
+            "Unable to process batch, reverted to the last checkpoint."
+        );
+        assert_eq!(checkpoints, vec![Some(String::from("a"))]);
+
+        // All changes of the second batch are reverted, including new accounts
+        assert_eq!(engine.accounts().unwrap(), checkpoint_accounts);
+        assert_eq!(engine.account(1).unwrap().unwrap().available_balance, 10.0);
+        assert_eq!(engine.account(3).unwrap(), None);
+        assert_eq!(
+            engine.stats(),
+            EngineStats {
+                processed: 2,
+                rejected: 0,
+                by_type: [(TransactionType::Deposit, 2)].into_iter().collect(),
+                by_source: HashMap::new(),
+            }
+        );
+    }
+
+    #[test]
+    async fn test_snapshot_bin_round_trip() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
                 deposit,1,1,10.0
-                deposit,1,2,25.0
-                dispute,1,2,
-                chargeback,1,2,
-                deposit,1,3,50.0
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,10.0,0.0,10.0,true
-            "
+                deposit,2,2,20.0
+                withdrawal,1,3,5.0
+                dispute,2,2,
+                deposit,3,4,7.5
+                dispute,3,4,
+                chargeback,3,4,
+                withdrawal,1,5,100.0
+            ",
         )
+        .await;
+
+        let mut snapshot = Vec::new();
+        engine.save_snapshot_bin(&mut snapshot).unwrap();
+
+        let restored = TransactionEngine::new();
+        restored.load_snapshot_bin(snapshot.as_slice()).unwrap();
+
+        assert_eq!(restored.accounts().unwrap(), engine.accounts().unwrap());
+        assert_eq!(restored.stats(), engine.stats());
+
+        // Restored ledgers can still be referenced
+        restored
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    resolve,2,2,
+                ",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            restored.account(2).unwrap().unwrap().available_balance,
+            20.0
+        );
     }
 
-    #[test(flavor = "multi_thread")]
-    async fn test_multiple_deposits_withdrawals() {
-        assert_csv_snapshot!(
+    // Compare reloading state from a binary snapshot with rebuilding it from CSV input.
+    // Run with `cargo test --release -- --ignored --nocapture bench_snapshot_bin_load`.
+    #[ignore]
+    #[test]
+    async fn bench_snapshot_bin_load() {
+        let mut csv = String::from("type,client,tx,amount\n");
+        for tx_id in 0..1_000_000u32 {
+            csv.push_str(&format!("deposit,{},{},1.5\n", tx_id % 50_000, tx_id));
+        }
+
+        let engine = TransactionEngine::new();
+        let start = Instant::now();
+        engine.process_csv_bytes(csv.as_bytes()).await.unwrap();
+        let text_load = start.elapsed();
+
+        let mut snapshot = Vec::new();
+        engine.save_snapshot_bin(&mut snapshot).unwrap();
+        let restored = TransactionEngine::new();
+        let start = Instant::now();
+        restored.load_snapshot_bin(snapshot.as_slice()).unwrap();
+        let bin_load = start.elapsed();
+
+        assert_eq!(restored.len().unwrap(), engine.len().unwrap());
+        println!(
+            "CSV: {} bytes, loaded in {:?}. Binary snapshot: {} bytes, loaded in {:?}.",
+            csv.len(),
+            text_load,
+            snapshot.len(),
+            bin_load
+        );
+    }
+
+    #[test]
+    async fn test_export_ledger() {
+        let engine = engine_from_csv(
             "
                 type,client,tx,amount
+                deposit,2,3,5.0
+                deposit,1,2,20.0
                 deposit,1,1,10.0
-                deposit,2,2,10.0
-                deposit,1,3,10.0
-                deposit,5,4,10.0
-                deposit,4,5,10.0
-                withdrawal,1,6,20.0
-                deposit,6,7,10.0
-                deposit,3,8,20.0
-                dispute,2,2
-                deposit,3,9,5.0
-                deposit,2,10,10.0
-                resolve,2,2
-                dispute,3,8
-                deposit,7,11,15.0
-                dispute,7,11
-                chargeback,7,11
-                deposit,7,12,20.0
-            "
-            =>
-            "
-                client,available,held,total,locked
-                1,0.0,0.0,0.0,false
-                2,20.0,0.0,20.0,false
-                3,5.0,20.0,25.0,false
-                4,10.0,0.0,10.0,false
-                5,10.0,0.0,10.0,false
-                6,10.0,0.0,10.0,false
-                7,0.0,0.0,0.0,true
-            "
+                withdrawal,2,4,2.5
+                dispute,1,2,
+            ",
         )
+        .await;
+
+        let mut ledger = Vec::new();
+        engine.export_ledger(&mut ledger).unwrap();
+
+        assert_eq!(
+            String::from_utf8(ledger).unwrap(),
+            "client,tx,amount,disputed\n\
+             1,1,10.0000,false\n\
+             1,2,20.0000,true\n\
+             2,3,5.0000,false\n\
+             2,4,2.5000,false\n"
+        );
+    }
+
+    #[test]
+    async fn test_balance_overflow() {
+        let engine = TransactionEngine::new();
+        let result = engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,3.0e38
+                    deposit,1,2,3.0e38
+                ",
+            ))
+            .await;
+
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<EngineError>(),
+            Some(&EngineError::Overflow {
+                client_id: 1,
+                tx_id: 2.into()
+            })
+        );
+
+        // The overflowing deposit must not have been applied
+        let account = engine.account(1).unwrap().unwrap();
+        assert_eq!(account.available_balance, 3.0e38);
+        assert!(!account.transactions.contains_key(&TxId::from(2)));
+    }
+
+    // Account store recording all calls made by the engine.
+    #[derive(Default)]
+    struct MockStore {
+        inner: InMemoryAccountStore,
+        calls: Mutex<Vec<&'static str>>,
+    }
+
+    impl AccountStore for MockStore {
+        fn get(&self, key: &AccountKey) -> anyhow::Result<Option<AccountHandle>> {
+            self.calls.lock().unwrap().push("get");
+            self.inner.get(key)
+        }
+
+        fn get_or_create(&self, key: &AccountKey) -> anyhow::Result<AccountHandle> {
+            self.calls.lock().unwrap().push("get_or_create");
+            self.inner.get_or_create(key)
+        }
+
+        fn remove(&self, key: &AccountKey) -> anyhow::Result<()> {
+            self.calls.lock().unwrap().push("remove");
+            self.inner.remove(key)
+        }
+
+        fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = AccountHandle> + '_>> {
+            self.calls.lock().unwrap().push("iter");
+            self.inner.iter()
+        }
+    }
+
+    #[test]
+    async fn test_custom_account_store() {
+        let engine = TransactionEngine::with_store(MockStore::default(), EngineConfig::default());
+        engine
+            .process_records(records_from_csv(
+                "
+                    type,client,tx,amount
+                    deposit,1,1,10.0
+                    deposit,2,2,20.0
+                ",
+            ))
+            .await
+            .unwrap();
+        let accounts = engine.accounts().unwrap();
+        let account = engine.account(2).unwrap().unwrap();
+
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(account.available_balance, 20.0);
+        assert_eq!(
+            *engine.accounts.calls.lock().unwrap(),
+            vec!["get_or_create", "get_or_create", "iter", "get"]
+        );
+    }
+
+    // Account store whose backend is unavailable.
+    struct FailingStore;
+
+    impl AccountStore for FailingStore {
+        fn get(&self, _: &AccountKey) -> anyhow::Result<Option<AccountHandle>> {
+            Err(anyhow::anyhow!("Store unavailable."))
+        }
+
+        fn get_or_create(&self, _: &AccountKey) -> anyhow::Result<AccountHandle> {
+            Err(anyhow::anyhow!("Store unavailable."))
+        }
+
+        fn remove(&self, _: &AccountKey) -> anyhow::Result<()> {
+            Err(anyhow::anyhow!("Store unavailable."))
+        }
+
+        fn iter(&self) -> anyhow::Result<Box<dyn Iterator<Item = AccountHandle> + '_>> {
+            Err(anyhow::anyhow!("Store unavailable."))
+        }
+    }
+
+    #[test]
+    async fn test_account_store_errors() {
+        let engine = TransactionEngine::with_store(FailingStore, EngineConfig::default());
+
+        // Store errors are returned instead of panicking
+        assert!(engine.account(1).is_err());
+        assert!(engine.with_account(1, Account::total_balance).is_err());
+        assert!(engine.clients_above(0.0).is_err());
+        assert!(engine.held_breakdown().is_err());
+        assert!(engine.deltas_since(&HashMap::new()).is_err());
+        assert!(engine.lock_where(|_| true).is_err());
+        assert!(engine.unlock_where(|_| true).is_err());
+        assert!(engine.aggregate_balances().is_err());
+        assert!(engine.len().is_err());
+        assert!(engine.is_empty().is_err());
     }
 }
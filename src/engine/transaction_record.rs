@@ -1,49 +1,304 @@
-use serde::Deserialize;
+use serde::{de, Deserialize, Deserializer};
+use std::io::Read;
 
-use super::TransactionType;
+use super::{
+    AccountKey, AmountUnit, InvalidReason, TransactionStatus, TransactionType, TxId,
+    ValidationError,
+};
 
 #[derive(Debug, Deserialize)]
+#[serde(try_from = "RawTransactionRecord")]
 pub struct TransactionRecord {
     pub r#type: TransactionType,
-    #[serde(rename = "client")]
     pub client_id: u16,
-    #[serde(rename = "tx")]
-    pub transaction_id: u32,
+    /// Id of the transaction. Disputes without a tx id in the input are read as tx id 0,
+    /// see `EngineConfig::dispute_latest_deposit`.
+    pub transaction_id: TxId,
     pub amount: Option<f32>,
+    /// Number of decimal places of the amount as written in the input, ignoring trailing zeros.
+    /// This is kept separately, since the parsed amount may not be able to represent all of them.
+    pub amount_places: Option<usize>,
+    /// Amount in integer cents, only used if the engine is configured for cents.
+    pub amount_cents: Option<u64>,
+    /// Tenant owning the client account. Records without a tenant belong to the default tenant.
+    pub tenant: Option<String>,
+    /// Name of the account holder, captured from the first applied record of the client.
+    pub client_name: Option<String>,
+    /// Currency of the record. The currency of the first applied record becomes the account's currency,
+    /// records in any other currency are rejected.
+    pub currency: Option<String>,
+    /// Batch the record belongs to, see `TransactionEngine::process_batches`.
+    pub batch: Option<String>,
+    /// Upstream status of the record. Records without a status are always applied.
+    pub status: Option<TransactionStatus>,
+    /// System the record originates from, e.g. the partner that sent it.
+    pub source: Option<String>,
+}
+
+/// A deposit without an amount for client 0 and tx 0, with all optional columns missing.
+///
+/// This is mainly useful to only spell out the relevant fields, e.g. `..Default::default()` in tests.
+impl Default for TransactionRecord {
+    fn default() -> Self {
+        TransactionRecord {
+            r#type: TransactionType::Deposit,
+            client_id: 0,
+            transaction_id: TxId::default(),
+            amount: None,
+            amount_places: None,
+            amount_cents: None,
+            tenant: None,
+            client_name: None,
+            currency: None,
+            batch: None,
+            status: None,
+            source: None,
+        }
+    }
+}
+
+/// A `TransactionRecord` as deserialized from the input, with the amount still carrying its precision.
+#[derive(Deserialize)]
+struct RawTransactionRecord {
+    r#type: TransactionType,
+    client: u16,
+    #[serde(default)]
+    tx: Option<TxId>,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    amount: Option<ParsedAmount>,
+    #[serde(default)]
+    amount_cents: Option<u64>,
+    #[serde(default)]
+    tenant: Option<String>,
+    #[serde(default)]
+    client_name: Option<String>,
+    #[serde(default)]
+    currency: Option<String>,
+    #[serde(default)]
+    batch: Option<String>,
+    #[serde(default)]
+    status: Option<TransactionStatus>,
+    #[serde(default)]
+    source: Option<String>,
+}
+
+impl TryFrom<RawTransactionRecord> for TransactionRecord {
+    type Error = String;
+
+    fn try_from(raw: RawTransactionRecord) -> Result<Self, Self::Error> {
+        // Only disputes may omit the tx id
+        let transaction_id = match (raw.tx, raw.r#type) {
+            (Some(tx_id), _) => tx_id,
+            (None, TransactionType::Dispute) => TxId::from(0),
+            (None, _) => return Err("missing field `tx`".to_string()),
+        };
+
+        Ok(TransactionRecord {
+            r#type: raw.r#type,
+            client_id: raw.client,
+            transaction_id,
+            amount: raw.amount.as_ref().map(|amount| amount.value),
+            amount_places: raw.amount.map(|amount| amount.places),
+            amount_cents: raw.amount_cents,
+            tenant: raw.tenant,
+            client_name: raw.client_name,
+            currency: raw.currency,
+            batch: raw.batch,
+            status: raw.status,
+            source: raw.source,
+        })
+    }
+}
+
+/// A parsed amount along with the number of decimal places it was written with.
+struct ParsedAmount {
+    value: f32,
+    places: usize,
 }
 
 impl TransactionRecord {
-    /// Validate the transaction.
+    /// Validate the transaction, returning the reason if it's invalid.
     ///
     /// Rules for transaction validity:
     /// 1. `type` IN (`deposit`, `withdrawal`) AND `amount` IS present => valid
-    /// 2. `type` IN (`dispute`, `resolution`, `chargeback`) AND `amount` IS NOT present => valid
+    /// 2. `type` IN (`dispute`, `resolution`, `chargeback`, `reversal`) AND `amount` IS NOT present => valid
+    /// 3. `type` IS `resolution` AND `amount` IS present => valid (partial resolve)
+    ///
+    /// All other cases are invalid, as are amounts that are NaN, infinite, zero or negative.
+    pub fn validate(&self) -> Result<(), InvalidReason> {
+        let amount = match (self.r#type, self.amount) {
+            (TransactionType::Deposit | TransactionType::Withdraw, None) => {
+                return Err(InvalidReason::MissingAmount)
+            }
+            // Partial resolves carry the amount to release
+            (
+                TransactionType::Deposit | TransactionType::Withdraw | TransactionType::Resolve,
+                Some(amount),
+            ) => amount,
+            (_, None) => return Ok(()),
+            (_, Some(_)) => return Err(InvalidReason::UnexpectedAmount),
+        };
+
+        // Non-finite amounts would silently poison the balances
+        if !amount.is_finite() {
+            return Err(InvalidReason::NonFiniteAmount);
+        }
+        if amount <= 0.0 {
+            return Err(InvalidReason::NonPositiveAmount);
+        }
+
+        Ok(())
+    }
+
+    /// Take the amount from the column of the given unit, converting cents to a decimal amount.
     ///
-    /// All other cases are invalid.
+    /// This has to happen before validation, since `validate` only checks `amount`.
+    pub fn normalize_amount(&mut self, unit: AmountUnit) {
+        if unit == AmountUnit::Cents {
+            // Divide in double precision, so the result is the closest amount to the exact value
+            self.amount = self.amount_cents.map(|cents| (cents as f64 / 100.0) as f32);
+            self.amount_places = None;
+        }
+    }
+
+    /// Whether the transaction is valid, see `validate`.
     pub fn is_valid(&self) -> bool {
-        let has_amount = self.amount.is_some();
-
-        // Define valid tx types with/without amount present
-        let valid_cases_with_amount = [TransactionType::Deposit, TransactionType::Withdraw];
-        let valid_cases_without_amount = [
-            TransactionType::Dispute,
-            TransactionType::Resolve,
-            TransactionType::Chargeback,
-        ];
+        self.validate().is_ok()
+    }
+
+    /// Return the key of the account this transaction belongs to.
+    pub fn account_key(&self) -> AccountKey {
+        AccountKey::new(self.tenant.clone(), self.client_id)
+    }
+
+    /// Return the number of decimal places of the amount, if present.
+    ///
+    /// This is based on the amount as written in the input if known, or the shortest representation
+    /// of the parsed amount otherwise. Trailing zeros are not counted either way.
+    pub fn decimal_places(&self) -> Option<usize> {
+        let amount = self.amount?;
+        Some(
+            self.amount_places
+                .unwrap_or_else(|| decimal_places(&amount.to_string())),
+        )
+    }
+}
+
+/// Return the number of decimal places of a formatted number, ignoring trailing zeros.
+fn decimal_places(number: &str) -> usize {
+    number
+        .split_once('.')
+        .map_or(0, |(_, fraction)| fraction.trim_end_matches('0').len())
+}
 
-        // Test tx type against predefined checklist
-        let valid_tx_with_amount = has_amount && valid_cases_with_amount.contains(&self.r#type);
-        let valid_tx_without_amount =
-            !has_amount && valid_cases_without_amount.contains(&self.r#type);
+/// Deserialize an amount, accepting surrounding whitespace, a leading `+` and a trailing dot.
+///
+/// Amounts are accepted as numbers as well as strings, e.g. `50.0`, `"+50.0"` or `" 50. "`.
+///
+/// Decimal places are counted before the amount is converted to `f32`, so digits beyond its precision still count.
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<Option<ParsedAmount>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum RawAmount {
+        Number(f64),
+        Text(String),
+    }
 
-        // Determine whether the transaction is valid
-        valid_tx_with_amount || valid_tx_without_amount
+    match Option::<RawAmount>::deserialize(deserializer)? {
+        Some(RawAmount::Number(amount)) => Ok(Some(ParsedAmount {
+            value: amount as f32,
+            places: decimal_places(&amount.to_string()),
+        })),
+        Some(RawAmount::Text(text)) => {
+            let text = text.trim();
+            if text.is_empty() {
+                return Ok(None);
+            }
+            let value = text
+                .strip_prefix('+')
+                .unwrap_or(text)
+                .parse()
+                .map_err(|_| de::Error::custom(format!("invalid amount '{}'", text)))?;
+            // Amounts in scientific notation only have a meaningful number of places once expanded
+            let places = if text.contains(['e', 'E']) {
+                text.parse::<f64>()
+                    .map_or(0, |amount| decimal_places(&amount.to_string()))
+            } else {
+                decimal_places(text)
+            };
+            Ok(Some(ParsedAmount { value, places }))
+        }
+        None => Ok(None),
     }
 }
 
+/// Filter out all invalid records, according to `TransactionRecord::is_valid`.
+///
+/// Amounts are normalized to the given unit first, like the engine does.
+/// This allows pre-filtering records before passing them to the engine,
+/// which would otherwise fail on the first invalid record.
+pub fn valid_records<I>(records: I, unit: AmountUnit) -> impl Iterator<Item = TransactionRecord>
+where
+    I: IntoIterator<Item = TransactionRecord>,
+{
+    records
+        .into_iter()
+        .map(move |mut tx| {
+            tx.normalize_amount(unit);
+            tx
+        })
+        .filter(TransactionRecord::is_valid)
+}
+
+/// Validate all records of the given CSV input without processing them.
+///
+/// The input is read the same way as by the CLI, i.e. with a header row and trimmed fields,
+/// with amounts in the given unit.
+/// Returns the line of the first record that can't be deserialized or isn't valid
+/// according to `TransactionRecord::is_valid`, along with the reason.
+pub fn validate_file<R: Read>(reader: R, unit: AmountUnit) -> Result<(), (usize, ValidationError)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(b',')
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    // Report CSV errors at their own position, if they have one
+    let unparsable = |line: usize, err: csv::Error| {
+        let line = err
+            .position()
+            .map_or(line, |position| position.line() as usize);
+        (line, ValidationError::Unparsable(err.to_string()))
+    };
+
+    let headers = reader.headers().map_err(|err| unparsable(1, err))?.clone();
+    let mut record = csv::StringRecord::new();
+    let mut line = 1;
+    while reader
+        .read_record(&mut record)
+        .map_err(|err| unparsable(line + 1, err))?
+    {
+        line = record
+            .position()
+            .map_or(line + 1, |position| position.line() as usize);
+        let mut tx: TransactionRecord = record
+            .deserialize(Some(&headers))
+            .map_err(|err| unparsable(line, err))?;
+        tx.normalize_amount(unit);
+        tx.validate()
+            .map_err(|reason| (line, ValidationError::Invalid(reason)))?;
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{TransactionRecord, TransactionType};
+    use super::{valid_records, validate_file, TransactionRecord, TransactionType};
+    use crate::engine::{AmountUnit, InvalidReason, TxId, ValidationError};
 
     #[test]
     fn test_is_valid() {
@@ -51,26 +306,37 @@ mod tests {
             TransactionRecord {
                 r#type: TransactionType::Deposit,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: Some(100.0),
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Withdraw,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: Some(100.0),
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Dispute,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: None,
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Chargeback,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: None,
+                ..Default::default()
+            },
+            TransactionRecord {
+                r#type: TransactionType::Resolve,
+                client_id: 1,
+                transaction_id: 1.into(),
+                amount: Some(1.23),
+                ..Default::default()
             },
         ];
 
@@ -82,26 +348,30 @@ mod tests {
             TransactionRecord {
                 r#type: TransactionType::Deposit,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: None,
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Withdraw,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: None,
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Dispute,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: Some(1.23),
+                ..Default::default()
             },
             TransactionRecord {
                 r#type: TransactionType::Chargeback,
                 client_id: 1,
-                transaction_id: 1,
+                transaction_id: 1.into(),
                 amount: Some(1.23),
+                ..Default::default()
             },
         ];
 
@@ -109,4 +379,244 @@ mod tests {
             assert!(!tx.is_valid());
         }
     }
+
+    #[test]
+    fn test_validate() {
+        let record = |r#type, amount| TransactionRecord {
+            r#type,
+            client_id: 1,
+            transaction_id: 1.into(),
+            amount,
+            ..Default::default()
+        };
+
+        let cases = [
+            (TransactionType::Deposit, Some(10.0), Ok(())),
+            (TransactionType::Resolve, None, Ok(())),
+            (TransactionType::Resolve, Some(2.5), Ok(())),
+            (
+                TransactionType::Deposit,
+                None,
+                Err(InvalidReason::MissingAmount),
+            ),
+            (
+                TransactionType::Withdraw,
+                None,
+                Err(InvalidReason::MissingAmount),
+            ),
+            (
+                TransactionType::Dispute,
+                Some(1.0),
+                Err(InvalidReason::UnexpectedAmount),
+            ),
+            (
+                TransactionType::Chargeback,
+                Some(1.0),
+                Err(InvalidReason::UnexpectedAmount),
+            ),
+            (
+                TransactionType::Reversal,
+                Some(1.0),
+                Err(InvalidReason::UnexpectedAmount),
+            ),
+            (
+                TransactionType::Deposit,
+                Some(0.0),
+                Err(InvalidReason::NonPositiveAmount),
+            ),
+            (
+                TransactionType::Withdraw,
+                Some(-5.0),
+                Err(InvalidReason::NonPositiveAmount),
+            ),
+            (
+                TransactionType::Resolve,
+                Some(-1.0),
+                Err(InvalidReason::NonPositiveAmount),
+            ),
+            (
+                TransactionType::Deposit,
+                Some(f32::NAN),
+                Err(InvalidReason::NonFiniteAmount),
+            ),
+            (
+                TransactionType::Withdraw,
+                Some(f32::NEG_INFINITY),
+                Err(InvalidReason::NonFiniteAmount),
+            ),
+        ];
+
+        for (r#type, amount, expected) in cases {
+            assert_eq!(
+                record(r#type, amount).validate(),
+                expected,
+                "{} with amount {:?}",
+                r#type,
+                amount
+            );
+        }
+    }
+
+    #[test]
+    fn test_non_finite_amounts() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,NaN\n\
+                     deposit,1,2,inf\n\
+                     withdrawal,1,3,-inf\n\
+                     resolve,1,4,NaN\n";
+        let records: Vec<TransactionRecord> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(records.len(), 4);
+        for tx in records {
+            assert!(!tx.is_valid());
+        }
+    }
+
+    #[test]
+    fn test_amount_formats() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,+50.0\n\
+                     deposit,1,2,50.\n\
+                     deposit,1,3,\" 50.0 \"\n\
+                     deposit,1,4,\" +50. \"\n\
+                     dispute,1,5,\"  \"\n";
+        let records: Vec<TransactionRecord> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        let amounts: Vec<Option<f32>> = records.iter().map(|tx| tx.amount).collect();
+        assert_eq!(
+            amounts,
+            vec![Some(50.0), Some(50.0), Some(50.0), Some(50.0), None]
+        );
+
+        // Anything else is still rejected
+        let input = "type,client,tx,amount\ndeposit,1,1,5O.0\n";
+        let record = csv::Reader::from_reader(input.as_bytes())
+            .deserialize::<TransactionRecord>()
+            .next()
+            .unwrap();
+        assert!(record.is_err());
+    }
+
+    #[test]
+    fn test_missing_tx() {
+        let input = "type,client,tx,amount\ndispute,1,,\ndeposit,1,,5.0\n";
+        let records: Vec<_> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize::<TransactionRecord>()
+            .collect();
+
+        // Only disputes may omit the tx id
+        assert_eq!(records[0].as_ref().unwrap().transaction_id, TxId::from(0));
+        assert!(records[1].is_err());
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,10.0\n\
+                     withdrawal,1,2,5.0\n\
+                     dispute,1,1,\n";
+        assert_eq!(validate_file(input.as_bytes(), AmountUnit::Decimal), Ok(()));
+
+        // Deposits without an amount are invalid
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,10.0\n\
+                     deposit,1,2,\n\
+                     deposit,1,3,\n";
+        assert_eq!(
+            validate_file(input.as_bytes(), AmountUnit::Decimal),
+            Err((3, ValidationError::Invalid(InvalidReason::MissingAmount)))
+        );
+
+        // Records that can't be deserialized are reported with the parse error
+        let input = "type,client,tx,amount\n\
+                     deposit,1,1,10.0\n\
+                     deposit,x,2,1.0\n";
+        match validate_file(input.as_bytes(), AmountUnit::Decimal) {
+            Err((3, ValidationError::Unparsable(_))) => {}
+            result => panic!("unexpected result: {:?}", result),
+        }
+
+        // Amounts are read from the column of the given unit
+        let input = "type,client,tx,amount_cents\n\
+                     deposit,1,1,1000\n\
+                     withdrawal,1,2,\n";
+        assert_eq!(
+            validate_file(input.as_bytes(), AmountUnit::Cents),
+            Err((3, ValidationError::Invalid(InvalidReason::MissingAmount)))
+        );
+    }
+
+    #[test]
+    fn test_decimal_places() {
+        let places = |amount| {
+            TransactionRecord {
+                r#type: TransactionType::Deposit,
+                client_id: 1,
+                transaction_id: 1.into(),
+                amount,
+                ..Default::default()
+            }
+            .decimal_places()
+        };
+
+        assert_eq!(places(None), None);
+        assert_eq!(places(Some(100.0)), Some(0));
+        assert_eq!(places(Some(1.5)), Some(1));
+        assert_eq!(places(Some(0.12345678)), Some(8));
+
+        // Deserialized amounts keep the places lost by the conversion to `f32`
+        let input = "type,client,tx,amount\n\
+                     withdrawal,1,1,100000.001\n\
+                     deposit,1,2,1.123456789\n\
+                     deposit,1,3,\"12345678.50\"\n\
+                     deposit,1,4,1.25e-3\n\
+                     deposit,1,5,250\n";
+        let places: Vec<Option<usize>> = csv::Reader::from_reader(input.as_bytes())
+            .deserialize::<TransactionRecord>()
+            .map(|tx| tx.unwrap().decimal_places())
+            .collect();
+        assert_eq!(places, vec![Some(3), Some(9), Some(1), Some(5), Some(0)]);
+    }
+
+    #[test]
+    fn test_valid_records() {
+        let record = |transaction_id: u32, r#type, amount| TransactionRecord {
+            r#type,
+            client_id: 1,
+            transaction_id: transaction_id.into(),
+            amount,
+            ..Default::default()
+        };
+        let records = vec![
+            record(1, TransactionType::Deposit, Some(10.0)),
+            record(2, TransactionType::Deposit, None),
+            record(3, TransactionType::Withdraw, Some(5.0)),
+            record(4, TransactionType::Dispute, Some(5.0)),
+            record(5, TransactionType::Dispute, None),
+        ];
+
+        let tx_ids: Vec<_> = valid_records(records, AmountUnit::Decimal)
+            .map(|tx| tx.transaction_id)
+            .collect();
+        assert_eq!(tx_ids, vec![TxId::from(1), TxId::from(3), TxId::from(5)]);
+
+        // In cents mode, amounts are taken from `amount_cents`
+        let records = vec![
+            TransactionRecord {
+                amount_cents: Some(1000),
+                ..record(1, TransactionType::Deposit, None)
+            },
+            record(2, TransactionType::Deposit, Some(10.0)),
+        ];
+        let amounts: Vec<_> = valid_records(records, AmountUnit::Cents)
+            .map(|tx| (tx.transaction_id, tx.amount))
+            .collect();
+        assert_eq!(amounts, vec![(TxId::from(1), Some(10.0))]);
+    }
 }
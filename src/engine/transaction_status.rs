@@ -0,0 +1,13 @@
+use serde::Deserialize;
+
+/// Upstream status of a transaction record, read from the optional `status` column.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TransactionStatus {
+    /// The transaction is final and should be applied.
+    Posted,
+    /// The transaction isn't final yet.
+    Pending,
+    /// The transaction was cancelled.
+    Cancelled,
+}
@@ -1,15 +1,192 @@
-use serde::Deserialize;
+use anyhow::{anyhow, Error};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
 
-#[derive(Debug, Deserialize, PartialEq)]
+/// Type of a transaction record, (de)serialized by its name, e.g. `withdrawal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TransactionType {
-    #[serde(rename = "deposit")]
     Deposit,
-    #[serde(rename = "withdrawal")]
     Withdraw,
-    #[serde(rename = "dispute")]
     Dispute,
-    #[serde(rename = "resolve")]
     Resolve,
-    #[serde(rename = "chargeback")]
     Chargeback,
+    /// Direct correction of an erroneous deposit, without any dispute.
+    Reversal,
+}
+
+impl TransactionType {
+    /// All transaction types.
+    const ALL: [TransactionType; 6] = [
+        TransactionType::Deposit,
+        TransactionType::Withdraw,
+        TransactionType::Dispute,
+        TransactionType::Resolve,
+        TransactionType::Chargeback,
+        TransactionType::Reversal,
+    ];
+
+    /// Return the name of the transaction type, e.g. `withdrawal`.
+    fn name(self) -> &'static str {
+        match self {
+            TransactionType::Deposit => "deposit",
+            TransactionType::Withdraw => "withdrawal",
+            TransactionType::Dispute => "dispute",
+            TransactionType::Resolve => "resolve",
+            TransactionType::Chargeback => "chargeback",
+            TransactionType::Reversal => "reversal",
+        }
+    }
+}
+
+impl fmt::Display for TransactionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for TransactionType {
+    type Err = Error;
+
+    /// Parse a transaction type, ignoring surrounding whitespace.
+    ///
+    /// Unknown types are rejected with a suggestion of the closest known type, if any is close enough.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        match s {
+            "deposit" => return Ok(TransactionType::Deposit),
+            "withdrawal" => return Ok(TransactionType::Withdraw),
+            "dispute" => return Ok(TransactionType::Dispute),
+            "resolve" => return Ok(TransactionType::Resolve),
+            "chargeback" => return Ok(TransactionType::Chargeback),
+            "reversal" => return Ok(TransactionType::Reversal),
+            _ => {}
+        }
+
+        // Suggest the closest known type for typos
+        let closest = Self::ALL
+            .into_iter()
+            .map(|tx_type| (edit_distance(s, tx_type.name()), tx_type))
+            .min_by_key(|(distance, _)| *distance);
+        match closest {
+            Some((distance, tx_type)) if distance <= 2 => Err(anyhow!(
+                "Unknown transaction type '{}', did you mean '{}'?",
+                s,
+                tx_type
+            )),
+            _ => Err(anyhow!(
+                "Unknown transaction type '{}', expected one of: deposit, withdrawal, dispute, resolve, chargeback, reversal.",
+                s
+            )),
+        }
+    }
+}
+
+impl Serialize for TransactionType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionType {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TransactionTypeVisitor)
+    }
+}
+
+/// Parses transaction types from borrowed or owned strings, without copying them.
+struct TransactionTypeVisitor;
+
+impl de::Visitor<'_> for TransactionTypeVisitor {
+    type Value = TransactionType;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a transaction type")
+    }
+
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Self::Value, E> {
+        s.parse().map_err(E::custom)
+    }
+}
+
+/// Return the Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, TransactionType};
+    use crate::engine::TransactionRecord;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            "deposit".parse::<TransactionType>().unwrap(),
+            TransactionType::Deposit
+        );
+        assert_eq!(
+            " withdrawal\t".parse::<TransactionType>().unwrap(),
+            TransactionType::Withdraw
+        );
+        assert_eq!(
+            "xyz".parse::<TransactionType>().unwrap_err().to_string(),
+            "Unknown transaction type 'xyz', expected one of: deposit, withdrawal, dispute, resolve, chargeback, reversal."
+        );
+    }
+
+    #[test]
+    fn test_names_round_trip() {
+        for tx_type in TransactionType::ALL {
+            assert_eq!(
+                tx_type.to_string().parse::<TransactionType>().unwrap(),
+                tx_type
+            );
+        }
+    }
+
+    #[test]
+    fn test_suggestion() {
+        assert_eq!(
+            "deposti"
+                .parse::<TransactionType>()
+                .unwrap_err()
+                .to_string(),
+            "Unknown transaction type 'deposti', did you mean 'deposit'?"
+        );
+
+        // The suggestion is part of the deserialization error
+        let input = "type,client,tx,amount\nchargebak,1,1,\n";
+        let err = csv::Reader::from_reader(input.as_bytes())
+            .deserialize::<TransactionRecord>()
+            .next()
+            .unwrap()
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Unknown transaction type 'chargebak', did you mean 'chargeback'?"));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("deposit", "deposit"), 0);
+        assert_eq!(edit_distance("deposti", "deposit"), 2);
+        assert_eq!(edit_distance("", "resolve"), 7);
+        assert_eq!(edit_distance("dispute", "resolve"), 5);
+    }
 }
@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, num::ParseIntError, str::FromStr};
+
+#[cfg(not(feature = "wide-tx-ids"))]
+type Repr = u32;
+
+#[cfg(feature = "wide-tx-ids")]
+type Repr = u64;
+
+/// Identifier of a transaction.
+///
+/// Ids are stored in 32 bits by default, which keeps ledgers compact. The `wide-tx-ids` feature
+/// stores them in 64 bits instead, for inputs whose id space doesn't fit into 32 bits.
+/// The API is the same regardless of the feature, it only changes which ids are representable.
+/// Snapshots are only compatible between builds using the same width.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct TxId(Repr);
+
+impl TxId {
+    /// Construct an id from a `u64`, returning `None` if it isn't representable in this build.
+    pub fn new(id: u64) -> Option<Self> {
+        Repr::try_from(id).ok().map(TxId)
+    }
+
+    /// Return the id as a `u64`.
+    // The conversion is a no-op with the `wide-tx-ids` feature
+    #[allow(clippy::useless_conversion)]
+    pub fn get(self) -> u64 {
+        self.0.into()
+    }
+}
+
+impl From<u32> for TxId {
+    // The conversion is a no-op without the `wide-tx-ids` feature
+    #[allow(clippy::useless_conversion)]
+    fn from(id: u32) -> Self {
+        TxId(id.into())
+    }
+}
+
+impl FromStr for TxId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(TxId)
+    }
+}
+
+impl fmt::Display for TxId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TxId;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(TxId::from(7).get(), 7);
+        assert_eq!("7".parse::<TxId>(), Ok(TxId::from(7)));
+        assert_eq!(TxId::new(u64::from(u32::MAX)), Some(TxId::from(u32::MAX)));
+
+        // Ids above 32 bits are only representable with the `wide-tx-ids` feature
+        let wide = TxId::new(u64::from(u32::MAX) + 1);
+        assert_eq!(wide.is_some(), cfg!(feature = "wide-tx-ids"));
+        assert_eq!(
+            "4294967296".parse::<TxId>().is_ok(),
+            cfg!(feature = "wide-tx-ids")
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use std::fmt;
+
+use super::InvalidReason;
+
+/// The reason a record failed validation, see `validate_file`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// The record couldn't be deserialized, with the underlying error message.
+    Unparsable(String),
+    /// The record was deserialized, but isn't valid according to `TransactionRecord::validate`.
+    Invalid(InvalidReason),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::Unparsable(message) => {
+                write!(f, "Unable to parse record: {}", message)
+            }
+            ValidationError::Invalid(reason) => write!(f, "Invalid transaction: {}", reason),
+        }
+    }
+}
@@ -0,0 +1,35 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// How disputes referencing a withdrawal are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WithdrawalDisputePolicy {
+    /// Disputes on withdrawals are ignored.
+    Ignore,
+    /// The withdrawal is treated as a reversal and its amount flows back
+    /// to the available balance. A resolve takes it out again, a chargeback
+    /// makes the reversal final.
+    ReverseToAvailable,
+    /// The withdrawal amount is moved from available to held funds,
+    /// exactly like a disputed deposit.
+    #[default]
+    Hold,
+}
+
+impl FromStr for WithdrawalDisputePolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(WithdrawalDisputePolicy::Ignore),
+            "reverse-to-available" => Ok(WithdrawalDisputePolicy::ReverseToAvailable),
+            "hold" => Ok(WithdrawalDisputePolicy::Hold),
+            _ => Err(anyhow!(
+                "Unknown withdrawal dispute policy '{}', expected one of: ignore, reverse-to-available, hold.",
+                s
+            )),
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Order in which withdrawals draw from the balances of an account.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WithdrawalOrder {
+    /// Withdrawals only draw from the available balance.
+    #[default]
+    AvailableFirst,
+    /// Withdrawals draw from the held balance first, the rest from the available balance.
+    ///
+    /// The drawn funds are taken from the held amounts of disputed transactions, oldest first,
+    /// so resolving or charging back a dispute afterwards only moves what's still held.
+    HeldFirst,
+}
+
+impl FromStr for WithdrawalOrder {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "available-first" => Ok(WithdrawalOrder::AvailableFirst),
+            "held-first" => Ok(WithdrawalOrder::HeldFirst),
+            _ => Err(anyhow!(
+                "Unknown withdrawal order '{}', expected one of: available-first, held-first.",
+                s
+            )),
+        }
+    }
+}
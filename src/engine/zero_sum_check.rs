@@ -0,0 +1,19 @@
+use serde::Deserialize;
+
+/// Expected aggregate total balance of all accounts, e.g. zero in a closed system
+/// where deposits and withdrawals net out against an external ledger.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZeroSumCheck {
+    /// Expected sum of all available and held balances.
+    pub expected: f64,
+    /// Maximum allowed absolute difference from the expected sum.
+    pub tolerance: f64,
+}
+
+impl ZeroSumCheck {
+    /// Check whether the given aggregate total is within tolerance of the expected value.
+    pub fn holds(&self, total: f64) -> bool {
+        (total - self.expected).abs() <= self.tolerance
+    }
+}
@@ -0,0 +1,7 @@
+pub mod engine;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+
+pub use engine::TransactionEngine;
@@ -1,51 +1,887 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 use clap::Parser;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{BufWriter, Read, Write},
+    net::{TcpListener, TcpStream},
+};
 
-mod engine;
-use engine::TransactionEngine;
+use tx_engine::engine::{
+    diff_accounts, Account, AccountDiff, AmountUnit, BoolFormat, Config, DecimalSeparator,
+    HeaderCase, OutputConfig, OutputFormat, PartialChargebackPolicy, TransactionEngine,
+    WithdrawalDisputePolicy, WithdrawalOrder,
+};
 
 #[derive(Parser)]
 struct Cli {
-    #[clap(parse(from_os_str))]
-    transaction_file: std::path::PathBuf,
+    #[clap(parse(from_os_str), required_unless_present = "listen")]
+    transaction_file: Option<std::path::PathBuf>,
+
+    /// Read transactions from the first TCP connection on the given address
+    /// instead of a file. Output is written once the connection is closed
+    #[clap(long, value_name = "ADDR", conflicts_with = "transaction-file")]
+    listen: Option<String>,
+
+    /// Write output to the given file instead of stdout
+    #[clap(short, long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+
+    /// Field delimiter of the input, e.g. `;` [default: ,]
+    #[clap(long)]
+    delimiter: Option<char>,
+
+    /// Decimal separator of the input amounts (dot, comma) [default: dot]
+    #[clap(long)]
+    decimal_separator: Option<DecimalSeparator>,
+
+    /// Path to a TOML config file. Command line flags override its values
+    #[clap(long, parse(from_os_str))]
+    config: Option<std::path::PathBuf>,
+
+    /// Number of decimal places for all balance columns [default: 4]
+    #[clap(long)]
+    display_scale: Option<usize>,
+
+    /// Number of decimal places for the `available` column [default: display scale]
+    #[clap(long)]
+    available_precision: Option<usize>,
+
+    /// Number of decimal places for the `held` column [default: display scale]
+    #[clap(long)]
+    held_precision: Option<usize>,
+
+    /// Number of decimal places for the `total` column [default: display scale]
+    #[clap(long)]
+    total_precision: Option<usize>,
+
+    /// Format of the output (csv, ndjson) [default: csv]
+    #[clap(long)]
+    output_format: Option<OutputFormat>,
+
+    /// Format of the `locked` column (true-false, yes-no, one-zero) [default: true-false]
+    #[clap(long)]
+    locked_format: Option<BoolFormat>,
+
+    /// Letter case of the header row (lower, upper, title) [default: lower]
+    #[clap(long)]
+    header_case: Option<HeaderCase>,
+
+    /// Add a `solvent` column, which is false for accounts with a negative total balance
+    #[clap(long)]
+    with_solvency: bool,
+
+    /// Add `client_name` and `currency` columns, as first seen for each client
+    #[clap(long)]
+    with_metadata: bool,
+
+    /// Add `deposit_count`, `withdrawal_count`, `total_deposited` and `total_withdrawn` columns
+    #[clap(long)]
+    with_activity: bool,
+
+    /// Lock accounts whose available balance becomes negative
+    #[clap(long)]
+    auto_lock_on_negative: bool,
+
+    /// Keep processing disputes, resolves and chargebacks on locked accounts
+    #[clap(long)]
+    process_disputes_on_locked: bool,
+
+    /// Keep processing resolves on locked accounts, releasing held funds while the account stays locked
+    #[clap(long)]
+    resolve_on_locked: bool,
+
+    /// Apply reversals even if they leave a negative available balance
+    #[clap(long)]
+    allow_overdrawing_reversals: bool,
+
+    /// Apply all records regardless of the lock state, e.g. to replay a historical ledger.
+    /// Dangerous for live data
+    #[clap(long)]
+    replay_mode: bool,
+
+    /// Skip records that can't be parsed instead of aborting, printing a warning with their line
+    #[clap(long)]
+    skip_unparsable_records: bool,
+
+    /// Ignore records of unknown clients unless they're deposits, instead of creating empty accounts
+    #[clap(long)]
+    require_opening_deposit: bool,
+
+    /// Interpret disputes with tx id 0 or without a tx id as disputing the client's most recent deposit
+    #[clap(long)]
+    dispute_latest_deposit: bool,
+
+    /// Available balance every account must retain after a withdrawal [default: 0]
+    #[clap(long, value_name = "AMOUNT")]
+    minimum_balance: Option<f32>,
+
+    /// Maximum number of open disputes per account [default: unlimited]
+    #[clap(long, value_name = "N")]
+    max_open_disputes: Option<usize>,
+
+    /// Maximum number of transactions kept per account for disputes [default: unlimited]
+    #[clap(long, value_name = "N")]
+    max_ledger_entries: Option<usize>,
+
+    /// Drop all ledgers after N records if no disputes were seen yet. Later disputes are an error
+    #[clap(long, value_name = "N")]
+    auto_stream_threshold: Option<usize>,
+
+    /// How disputes on withdrawals are handled (ignore, reverse-to-available, hold) [default: hold]
+    #[clap(long)]
+    withdrawal_dispute_policy: Option<WithdrawalDisputePolicy>,
+
+    /// Order in which withdrawals draw from the balances (available-first, held-first) [default: available-first]
+    #[clap(long)]
+    withdrawal_order: Option<WithdrawalOrder>,
+
+    /// How disputes on partially charged back transactions are handled (ignore, dispute-remainder) [default: dispute-remainder]
+    #[clap(long)]
+    partial_chargeback_policy: Option<PartialChargebackPolicy>,
+
+    /// Unit of the input amounts (decimal, cents). Cents are read from the `amount_cents` column [default: decimal]
+    #[clap(long)]
+    amount_unit: Option<AmountUnit>,
+
+    /// Round all input amounts to N decimal places before applying them [default: unrounded]
+    #[clap(long, value_name = "N")]
+    ingest_rounding: Option<usize>,
+
+    /// Maximum number of decimal places for deposit amounts [default: unlimited]
+    #[clap(long, value_name = "N")]
+    deposit_precision: Option<usize>,
+
+    /// Maximum number of decimal places for withdrawal amounts [default: unlimited]
+    #[clap(long, value_name = "N")]
+    withdrawal_precision: Option<usize>,
+
+    /// Maximum amount of a single deposit or withdrawal [default: unlimited]
+    #[clap(long, value_name = "AMOUNT")]
+    max_transaction_amount: Option<f32>,
+
+    /// Print the number of processed records to stderr every N records
+    #[clap(long, value_name = "N")]
+    progress: Option<usize>,
+
+    /// Stream output while processing, keeping only one account in memory.
+    /// Requires dispute-free input grouped by client.
+    #[clap(long)]
+    streaming: bool,
+
+    /// Write every skipped record as a JSON line with its row, client, tx, type and reason
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with = "streaming"
+    )]
+    error_report: Option<std::path::PathBuf>,
+
+    /// Print a stable hash of the final account state to stderr, e.g. to compare runs
+    #[clap(long, conflicts_with = "streaming")]
+    print_hash: bool,
+
+    /// Only write accounts whose balances or lock state differ from the given binary snapshot
+    #[clap(
+        long,
+        value_name = "PATH",
+        parse(from_os_str),
+        conflicts_with = "streaming"
+    )]
+    baseline: Option<std::path::PathBuf>,
+}
+
+impl Cli {
+    /// Build the config from the config file, overridden by command line flags.
+    fn config(&self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+
+        // Engine config
+        if self.auto_lock_on_negative {
+            config.engine.auto_lock_on_negative = true;
+        }
+        if self.process_disputes_on_locked {
+            config.engine.process_disputes_on_locked = true;
+        }
+        if self.resolve_on_locked {
+            config.engine.resolve_on_locked = true;
+        }
+        if self.allow_overdrawing_reversals {
+            config.engine.allow_overdrawing_reversals = true;
+        }
+        if self.replay_mode {
+            config.engine.replay_mode = true;
+        }
+        if self.skip_unparsable_records {
+            config.engine.skip_unparsable_records = true;
+        }
+        if self.require_opening_deposit {
+            config.engine.require_opening_deposit = true;
+        }
+        if self.dispute_latest_deposit {
+            config.engine.dispute_latest_deposit = true;
+        }
+        if let Some(minimum_balance) = self.minimum_balance {
+            config.engine.minimum_balance = minimum_balance;
+        }
+        if let Some(max_open_disputes) = self.max_open_disputes {
+            config.engine.max_open_disputes = Some(max_open_disputes);
+        }
+        if let Some(max_ledger_entries) = self.max_ledger_entries {
+            config.engine.max_ledger_entries = Some(max_ledger_entries);
+        }
+        if let Some(threshold) = self.auto_stream_threshold {
+            config.engine.auto_stream_threshold = Some(threshold);
+        }
+        if let Some(policy) = self.withdrawal_dispute_policy {
+            config.engine.withdrawal_dispute_policy = policy;
+        }
+        if let Some(order) = self.withdrawal_order {
+            config.engine.withdrawal_order = order;
+        }
+        if let Some(policy) = self.partial_chargeback_policy {
+            config.engine.partial_chargeback_policy = policy;
+        }
+        if let Some(amount_unit) = self.amount_unit {
+            config.engine.amount_unit = amount_unit;
+        }
+        if let Some(places) = self.ingest_rounding {
+            config.engine.ingest_rounding = Some(places);
+        }
+        if let Some(precision) = self.deposit_precision {
+            config.engine.deposit_precision = Some(precision);
+        }
+        if let Some(precision) = self.withdrawal_precision {
+            config.engine.withdrawal_precision = Some(precision);
+        }
+        if let Some(max_amount) = self.max_transaction_amount {
+            config.engine.max_transaction_amount = Some(max_amount);
+        }
+
+        // Output config
+        if let Some(scale) = self.display_scale {
+            config.output.display_scale = scale;
+        }
+        if let Some(precision) = self.available_precision {
+            config.output.available_precision = Some(precision);
+        }
+        if let Some(precision) = self.held_precision {
+            config.output.held_precision = Some(precision);
+        }
+        if let Some(precision) = self.total_precision {
+            config.output.total_precision = Some(precision);
+        }
+        if let Some(format) = self.output_format {
+            config.output.format = format;
+        }
+        if let Some(locked_format) = self.locked_format {
+            config.output.locked_format = locked_format;
+        }
+        if let Some(header_case) = self.header_case {
+            config.output.header_case = header_case;
+        }
+        if self.with_solvency {
+            config.output.with_solvency = true;
+        }
+        if self.with_metadata {
+            config.output.with_metadata = true;
+        }
+        if self.with_activity {
+            config.output.with_activity = true;
+        }
+
+        Ok(config)
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Cli::parse();
 
-    // Create reader from file
-    let reader = csv::ReaderBuilder::new()
-        .delimiter(b',')
-        .flexible(true)
-        .trim(csv::Trim::All)
-        .from_path(args.transaction_file)
-        .context("Unable to read transaction file.")?;
+    // Build config from config file and arguments
+    let Config {
+        engine: engine_config,
+        output: output_config,
+    } = args.config()?;
+
+    // Open input from a TCP connection or the transaction file
+    let input: Box<dyn Read + Send + Sync> = match (&args.listen, &args.transaction_file) {
+        (Some(addr), _) => {
+            let listener = TcpListener::bind(addr).context("Unable to listen on address.")?;
+            eprintln!("Listening on {}", listener.local_addr()?);
+            Box::new(accept_connection(&listener)?)
+        }
+        (None, Some(path)) => {
+            Box::new(File::open(path).context("Unable to read transaction file.")?)
+        }
+        (None, None) => unreachable!("clap requires either a transaction file or --listen"),
+    };
+
+    // Create reader from input
+    let delimiter = args.delimiter.unwrap_or(',');
+    if !delimiter.is_ascii() {
+        return Err(anyhow!(
+            "Delimiter '{}' must be an ASCII character.",
+            delimiter
+        ));
+    }
+    let mut reader = build_csv_reader(input, delimiter as u8);
+    let decimal_separator = args.decimal_separator.unwrap_or_default();
 
     // Initialize tx engine
-    let engine = TransactionEngine::new();
+    let mut engine = TransactionEngine::with_config(engine_config);
+
+    // Report progress to stderr if requested
+    if let Some(interval) = args.progress {
+        engine = engine.with_progress_callback(interval, |count| {
+            eprintln!("Processed {} records", count);
+        });
+    }
+
+    // Open output file, or fall back to stdout
+    let output: Box<dyn Write + Send> = match &args.output {
+        Some(path) => Box::new(File::create(path).context("Unable to create output file.")?),
+        None => Box::new(std::io::stdout()),
+    };
+
+    // Stream records straight to the output if requested
+    if args.streaming {
+        let headers = reader.headers()?.clone();
+        let records = reader
+            .into_records()
+            .map(move |record| decimal_separator.deserialize_record(&record?, &headers));
+        engine
+            .process_and_stream_output(records, &output_config, output)
+            .await?;
+        return Ok(());
+    }
+
+    // Open error report file if requested
+    let mut error_report = match &args.error_report {
+        Some(path) => Some(BufWriter::new(
+            File::create(path).context("Unable to create error report file.")?,
+        )),
+        None => None,
+    };
 
     // Process all records
-    engine.process_records(reader.into_deserialize()).await?;
+    process_csv(
+        &engine,
+        reader,
+        decimal_separator,
+        error_report.as_mut().map(|report| report as &mut dyn Write),
+    )
+    .await?;
+
+    // Check the aggregate balance if configured, before reporting warnings
+    engine.check_zero_sum()?;
+
+    // Report warnings to stderr
+    for warning in engine.warnings() {
+        eprintln!("Warning: {}", warning);
+    }
+
+    // Report the state hash to stderr if requested
+    if args.print_hash {
+        eprintln!("State hash: {:016x}", engine.state_hash()?);
+    }
 
-    // Write output to stdout
-    write_output_csv(&engine)?;
+    // Only keep accounts that changed since the baseline, if given
+    let mut accounts = engine.finalize()?;
+    if let Some(path) = &args.baseline {
+        let baseline = load_baseline(File::open(path).context("Unable to read baseline file.")?)?;
+        accounts = changed_accounts(accounts, &baseline);
+    }
+
+    // Write final accounts
+    match output_config.format {
+        OutputFormat::Csv => write_output_csv(accounts, &output_config, output)?,
+        OutputFormat::Ndjson => write_output_ndjson(accounts, &output_config, output)?,
+    }
 
     Ok(())
 }
 
-fn write_output_csv(engine: &TransactionEngine) -> Result<()> {
-    // Build CSV writer
-    let mut writer = csv::WriterBuilder::new()
+fn accept_connection(listener: &TcpListener) -> Result<TcpStream> {
+    let (stream, _) = listener.accept().context("Unable to accept connection.")?;
+    Ok(stream)
+}
+
+fn build_csv_reader<R: Read>(input: R, delimiter: u8) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(input)
+}
+
+/// Process all records of the given reader, reporting the line of the offending record on errors.
+///
+/// Amounts are read with the given decimal separator.
+/// Skipped records are written to the error report as JSON lines, if given.
+async fn process_csv<R: Read>(
+    engine: &TransactionEngine,
+    mut reader: csv::Reader<R>,
+    decimal_separator: DecimalSeparator,
+    mut error_report: Option<&mut dyn Write>,
+) -> Result<()> {
+    let headers = reader.headers()?.clone();
+    let mut record = csv::StringRecord::new();
+    while reader.read_record(&mut record)? {
+        let line = record.position().map_or(0, |position| position.line());
+        let tx = match decimal_separator.deserialize_record(&record, &headers) {
+            Ok(tx) => tx,
+            Err(err) => {
+                let error = err.to_string();
+                engine
+                    .handle_unparsable_record(line, err.into())
+                    .with_context(|| format!("Unable to parse record on line {}.", line))?;
+
+                // Report skipped unparsable records
+                if let Some(report) = error_report.as_mut() {
+                    writeln!(
+                        report,
+                        r#"{{"row":{},"reason":"unparsable_record","error":{}}}"#,
+                        line,
+                        serde_json::to_string(&error).context("Unable to serialize error.")?
+                    )
+                    .context("Unable to write error report.")?;
+                }
+                continue;
+            }
+        };
+        let (client_id, tx_id, tx_type) = (tx.client_id, tx.transaction_id, tx.r#type);
+        let outcome = engine
+            .process_record(tx)
+            .await
+            .with_context(|| format!("Unable to process record on line {}.", line))?;
+
+        // Report skipped records
+        if let (Some(reason), Some(report)) = (outcome, error_report.as_mut()) {
+            writeln!(
+                report,
+                r#"{{"row":{},"client":{},"tx":{},"type":"{}","reason":"{}"}}"#,
+                line, client_id, tx_id, tx_type, reason
+            )
+            .context("Unable to write error report.")?;
+        }
+    }
+
+    // Flush explicitly, since errors are silently ignored when the writer is dropped
+    if let Some(report) = error_report {
+        report.flush().context("Unable to write error report.")?;
+    }
+
+    Ok(())
+}
+
+/// Read the accounts of a binary snapshot, as written by `TransactionEngine::save_snapshot_bin`.
+fn load_baseline<R: Read>(input: R) -> Result<Vec<Account>> {
+    let baseline = TransactionEngine::new();
+    baseline.load_snapshot_bin(input)?;
+    baseline.finalize()
+}
+
+/// Keep only accounts that were added or changed compared to the baseline.
+fn changed_accounts(accounts: Vec<Account>, baseline: &[Account]) -> Vec<Account> {
+    let changed: HashSet<_> = diff_accounts(baseline, &accounts)
+        .into_iter()
+        .filter_map(|diff| match diff {
+            AccountDiff::Added(key) | AccountDiff::Changed { key, .. } => Some(key),
+            AccountDiff::Removed(_) => None,
+        })
+        .collect();
+    accounts
+        .into_iter()
+        .filter(|account| changed.contains(&account.key()))
+        .collect()
+}
+
+fn build_csv_writer<W: Write>(output: W) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
         .delimiter(b',')
-        .has_headers(true)
+        .has_headers(false)
         .flexible(false)
-        .from_writer(std::io::stdout());
+        .from_writer(output)
+}
+
+fn write_output_csv<W: Write>(
+    accounts: Vec<Account>,
+    config: &OutputConfig,
+    output: W,
+) -> Result<()> {
+    // Build CSV writer
+    let mut writer = build_csv_writer(output);
+
+    // Always write the header row, even if there are no accounts
+    writer.write_record(config.headers())?;
 
     // Serialize all account records
-    for account in engine.accounts()? {
-        writer.serialize(account)?;
+    for account in accounts {
+        writer.serialize(account.output(config))?;
     }
 
+    // Flush explicitly, since errors are silently ignored when the writer is dropped
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn write_output_ndjson<W: Write>(
+    accounts: Vec<Account>,
+    config: &OutputConfig,
+    output: W,
+) -> Result<()> {
+    let mut output = BufWriter::new(output);
+
+    // Write one JSON object per line, no header is needed
+    for account in accounts {
+        write_ndjson_line(&account, config, &mut output)?;
+    }
+
+    // Flush explicitly, since errors are silently ignored when the writer is dropped
+    output.flush()?;
+
+    Ok(())
+}
+
+fn write_ndjson_line<W: Write>(
+    account: &Account,
+    config: &OutputConfig,
+    output: &mut W,
+) -> Result<()> {
+    let line =
+        serde_json::to_string(&account.output(config)).context("Unable to serialize account.")?;
+    writeln!(output, "{}", line)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        accept_connection, build_csv_reader, changed_accounts, load_baseline, process_csv,
+        write_output_csv, write_output_ndjson,
+    };
+    use std::{
+        fs::File,
+        io::{self, Write},
+        net::{TcpListener, TcpStream},
+    };
+    use tx_engine::engine::{
+        DecimalSeparator, EngineConfig, HeaderCase, OutputConfig, TransactionEngine,
+    };
+
+    // Writer recording flushes, optionally failing them.
+    struct FlushRecorder {
+        flushed: bool,
+        fail_flush: bool,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed = true;
+            if self.fail_flush {
+                return Err(io::Error::other("flush failed"));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_output_to_file() {
+        let engine = TransactionEngine::new();
+        let input = "type,client,tx,amount\ndeposit,1,1,10.0\n";
+        let reader = csv::ReaderBuilder::new()
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .from_reader(input.as_bytes());
+        engine
+            .process_records(reader.into_deserialize())
+            .await
+            .unwrap();
+
+        let path =
+            std::env::temp_dir().join(format!("tx-engine-output-{}.csv", std::process::id()));
+        write_output_csv(
+            engine.finalize().unwrap(),
+            &OutputConfig::default(),
+            File::create(&path).unwrap(),
+        )
+        .unwrap();
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            output,
+            "client,available,held,total,locked\n1,10.0000,0.0000,10.0000,false\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_line_number() {
+        let input = "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,1,2,\n";
+        let engine = TransactionEngine::new();
+        let err = process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b','),
+            DecimalSeparator::Dot,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Unable to process record on line 3.");
+
+        let input =
+            "type,client,tx,amount\ndeposit,1,1,10.0\nwithdrawal,1,2,1.0\ndeposit,x,3,1.0\n";
+        let engine = TransactionEngine::new();
+        let err = process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b','),
+            DecimalSeparator::Dot,
+            None,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Unable to parse record on line 4.");
+    }
+
+    #[tokio::test]
+    async fn test_empty_input() {
+        // Both empty and header-only input result in zero accounts
+        for input in ["", "type,client,tx,amount\n"] {
+            let engine = TransactionEngine::new();
+            process_csv(
+                &engine,
+                build_csv_reader(input.as_bytes(), b','),
+                DecimalSeparator::Dot,
+                None,
+            )
+            .await
+            .unwrap();
+
+            let mut output = Vec::new();
+            write_output_csv(
+                engine.accounts().unwrap(),
+                &OutputConfig::default(),
+                &mut output,
+            )
+            .unwrap();
+            assert_eq!(
+                String::from_utf8(output).unwrap(),
+                "client,available,held,total,locked\n"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_report() {
+        let input = "type,client,tx,amount
+deposit,1,1,10.0
+withdrawal,1,2,50.0
+dispute,1,9,
+";
+        let engine = TransactionEngine::new();
+        let mut report = Vec::new();
+        process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b','),
+            DecimalSeparator::Dot,
+            Some(&mut report),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(report).unwrap(),
+            "{\"row\":3,\"client\":1,\"tx\":2,\"type\":\"withdrawal\",\"reason\":\"insufficient_funds\"}\n\
+             {\"row\":4,\"client\":1,\"tx\":9,\"type\":\"dispute\",\"reason\":\"unknown_transaction\"}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_error_report_unparsable() {
+        let input = "type,client,tx,amount
+deposit,1,1,10.0
+deposit,x,2,5.0
+";
+        let engine = TransactionEngine::with_config(EngineConfig {
+            skip_unparsable_records: true,
+            ..Default::default()
+        });
+        let mut report = Vec::new();
+        process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b','),
+            DecimalSeparator::Dot,
+            Some(&mut report),
+        )
+        .await
+        .unwrap();
+
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.starts_with(r#"{"row":3,"reason":"unparsable_record","error":""#));
+        assert!(report.ends_with("\"}\n"));
+        assert_eq!(report.lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_changed_accounts() {
+        let input = "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,20.0\ndeposit,3,3,30.0\n";
+        let engine = TransactionEngine::new();
+        engine.process_csv_bytes(input.as_bytes()).await.unwrap();
+        let mut snapshot = Vec::new();
+        engine.save_snapshot_bin(&mut snapshot).unwrap();
+        let baseline = load_baseline(snapshot.as_slice()).unwrap();
+
+        // Only the second client changes afterwards
+        engine
+            .process_csv_bytes(b"type,client,tx,amount\nwithdrawal,2,4,5.0\n")
+            .await
+            .unwrap();
+        let accounts = changed_accounts(engine.finalize().unwrap(), &baseline);
+
+        let mut output = Vec::new();
+        write_output_csv(accounts, &OutputConfig::default(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n2,15.0000,0.0000,15.0000,false\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decimal_comma() {
+        let input =
+            "type;client;tx;amount\ndeposit;1;1;50,25\nwithdrawal;1;2;10,5\ndeposit;2;3;7\n";
+        let engine = TransactionEngine::new();
+        process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b';'),
+            DecimalSeparator::Comma,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut output = Vec::new();
+        write_output_csv(
+            engine.finalize().unwrap(),
+            &OutputConfig::default(),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n\
+             1,39.7500,0.0000,39.7500,false\n\
+             2,7.0000,0.0000,7.0000,false\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_write_output_ndjson() {
+        let input = "type,client,tx,amount\ndeposit,1,1,10.0\ndeposit,2,2,2.5\n";
+        let engine = TransactionEngine::new();
+        process_csv(
+            &engine,
+            build_csv_reader(input.as_bytes(), b','),
+            DecimalSeparator::Dot,
+            None,
+        )
+        .await
+        .unwrap();
+
+        let mut output = Vec::new();
+        write_output_ndjson(
+            engine.finalize().unwrap(),
+            &OutputConfig::default(),
+            &mut output,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        // Every line is a JSON object on its own
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert!(value.is_object());
+        }
+        assert_eq!(
+            lines[0],
+            r#"{"client":1,"available":"10.0000","held":"0.0000","total":"10.0000","locked":"false"}"#
+        );
+    }
+
+    #[test]
+    fn test_write_output_flushes() {
+        let mut recorder = FlushRecorder {
+            flushed: false,
+            fail_flush: false,
+        };
+        write_output_csv(Vec::new(), &OutputConfig::default(), &mut recorder).unwrap();
+        assert!(recorder.flushed);
+
+        // Flush errors must be propagated instead of being swallowed on drop
+        let mut recorder = FlushRecorder {
+            flushed: false,
+            fail_flush: true,
+        };
+        assert!(write_output_csv(Vec::new(), &OutputConfig::default(), &mut recorder).is_err());
+    }
+
+    #[test]
+    fn test_write_output_title_case_header() {
+        let config = OutputConfig {
+            header_case: HeaderCase::Title,
+            ..OutputConfig::default()
+        };
+        let mut output = Vec::new();
+        write_output_csv(Vec::new(), &config, &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Client,Available,Held,Total,Locked\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_process_tcp_connection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // Send records from another thread and disconnect
+        let sender = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"type,client,tx,amount\ndeposit,1,1,10.0\n")
+                .unwrap();
+            stream.write_all(b"withdrawal,1,2,2.5\n").unwrap();
+        });
+
+        let engine = TransactionEngine::new();
+        let reader = build_csv_reader(accept_connection(&listener).unwrap(), b',');
+        engine
+            .process_records(reader.into_deserialize())
+            .await
+            .unwrap();
+        sender.join().unwrap();
+
+        let mut output = Vec::new();
+        write_output_csv(
+            engine.finalize().unwrap(),
+            &OutputConfig::default(),
+            &mut output,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "client,available,held,total,locked\n1,7.5000,0.0000,7.5000,false\n"
+        );
+    }
+}
@@ -0,0 +1,271 @@
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+use crate::engine::{TransactionEngine, TransactionRecord};
+
+/// Status line and JSON body of a response.
+type Response = (&'static str, String);
+
+/// Maximum size of a request body in bytes.
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// Maximum length of the request line and of every header line in bytes.
+const MAX_LINE_LENGTH: usize = 8 * 1024;
+
+/// Maximum number of header lines of a request.
+const MAX_HEADERS: usize = 64;
+
+/// Serve a minimal HTTP API for the given engine on the given listener.
+///
+/// Routes:
+/// - `POST /transactions` processes the JSON transaction record in the request body.
+/// - `GET /accounts/{id}` returns the account of the given client of the default tenant.
+///
+/// Every connection handles a single request. This only returns if accepting a connection fails.
+pub async fn serve(engine: Arc<TransactionEngine>, listener: TcpListener) -> Result<()> {
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Unable to accept connection.")?;
+        let engine = Arc::clone(&engine);
+        tokio::spawn(async move {
+            // Errors only affect the connection they occurred on
+            let _ = handle_connection(&engine, stream).await;
+        });
+    }
+}
+
+/// Read a single request from the stream and write the response.
+async fn handle_connection(engine: &TransactionEngine, stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+
+    // Write response, requests exceeding the limits are answered without routing them
+    let (status, body) = match read_request(&mut reader).await? {
+        Ok((request_line, body)) => route(engine, &request_line, &body).await,
+        Err(response) => response,
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+
+    Ok(())
+}
+
+/// Read the request line and body of a request,
+/// or return the error response if the request exceeds the limits.
+async fn read_request(
+    reader: &mut BufReader<TcpStream>,
+) -> Result<std::result::Result<(String, Vec<u8>), Response>> {
+    let too_large = || {
+        error(
+            "431 Request Header Fields Too Large",
+            "Request header too large.",
+        )
+    };
+
+    // Read request line
+    let request_line = match read_line(reader).await? {
+        Some(line) => line,
+        None => return Ok(Err(too_large())),
+    };
+
+    // Read headers, only the content length is relevant
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let line = match read_line(reader).await? {
+            Some(line) => line,
+            None => return Ok(Err(too_large())),
+        };
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HEADERS {
+            return Ok(Err(too_large()));
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = match value.trim().parse() {
+                    Ok(content_length) => content_length,
+                    Err(_) => return Ok(Err(error("400 Bad Request", "Invalid content length."))),
+                };
+            }
+        }
+    }
+
+    // Read body, unless it's too large to be a single record
+    if content_length > MAX_BODY_SIZE {
+        return Ok(Err(error(
+            "413 Payload Too Large",
+            "Request body too large.",
+        )));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Ok((request_line, body)))
+}
+
+/// Read a line of at most `MAX_LINE_LENGTH` bytes, returning `None` if it's longer.
+async fn read_line(reader: &mut BufReader<TcpStream>) -> Result<Option<String>> {
+    let mut line = String::new();
+    let read = (&mut *reader)
+        .take(MAX_LINE_LENGTH as u64)
+        .read_line(&mut line)
+        .await?;
+    if read == MAX_LINE_LENGTH && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+/// Dispatch a request to its route.
+async fn route(engine: &TransactionEngine, request_line: &str, body: &[u8]) -> Response {
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("POST"), Some("/transactions")) => post_transaction(engine, body).await,
+        (Some("GET"), Some(path)) => match path.strip_prefix("/accounts/") {
+            Some(client_id) => get_account(engine, client_id),
+            None => error("404 Not Found", "Not found."),
+        },
+        _ => error("404 Not Found", "Not found."),
+    }
+}
+
+/// Process the transaction record in the request body.
+async fn post_transaction(engine: &TransactionEngine, body: &[u8]) -> Response {
+    let tx: TransactionRecord = match serde_json::from_slice(body) {
+        Ok(tx) => tx,
+        Err(_) => return error("400 Bad Request", "Unable to parse transaction."),
+    };
+
+    match engine.process_record(tx).await {
+        Ok(None) => ("200 OK", r#"{"status":"applied"}"#.to_string()),
+        Ok(Some(reason)) => (
+            "200 OK",
+            format!(r#"{{"status":"rejected","reason":"{}"}}"#, reason),
+        ),
+        Err(err) => error("422 Unprocessable Entity", &err.to_string()),
+    }
+}
+
+/// Return the account of the given client.
+fn get_account(engine: &TransactionEngine, client_id: &str) -> Response {
+    let client_id: u16 = match client_id.parse() {
+        Ok(client_id) => client_id,
+        Err(_) => return error("400 Bad Request", "Invalid client id."),
+    };
+
+    let account = match engine.account(client_id) {
+        Ok(Some(account)) => account,
+        Ok(None) => return error("404 Not Found", "Account not found."),
+        Err(_) => return error("500 Internal Server Error", "Unable to read account."),
+    };
+    match serde_json::to_string(&account) {
+        Ok(account) => ("200 OK", account),
+        Err(_) => error("500 Internal Server Error", "Unable to serialize account."),
+    }
+}
+
+/// Build an error response with the given message.
+fn error(status: &'static str, message: &str) -> Response {
+    let message = serde_json::to_string(message).unwrap_or_default();
+    (status, format!(r#"{{"error":{}}}"#, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{serve, MAX_LINE_LENGTH};
+    use crate::TransactionEngine;
+    use std::sync::Arc;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    // Helper function to send a single request and return the raw response.
+    async fn request(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_deposit_and_query_balance() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(Arc::new(TransactionEngine::new()), listener));
+
+        let body = r#"{"type":"deposit","client":1,"tx":1,"amount":25.5}"#;
+        let response = request(
+            addr,
+            &format!(
+                "POST /transactions HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            ),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(r#"{"status":"applied"}"#));
+
+        let response = request(addr, "GET /accounts/1 HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.ends_with(
+            r#"{"client":1,"available":"25.5000","held":"0.0000","total":"25.5000","locked":"false"}"#
+        ));
+
+        let response = request(addr, "GET /accounts/2 HTTP/1.1\r\n\r\n").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_request_limits() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(serve(Arc::new(TransactionEngine::new()), listener));
+
+        // Huge bodies are refused before allocating them
+        let response = request(
+            addr,
+            "POST /transactions HTTP/1.1\r\nContent-Length: 1000000000000\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 413 Payload Too Large\r\n"));
+
+        // Malformed content lengths are answered instead of dropping the connection
+        let response = request(
+            addr,
+            "POST /transactions HTTP/1.1\r\nContent-Length: ten\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(response.ends_with(r#"{"error":"Invalid content length."}"#));
+
+        // Header lines are read up to the limit only
+        let response = request(
+            addr,
+            &format!(
+                "GET /accounts/1 HTTP/1.1\r\nX-Padding: {}",
+                "a".repeat(MAX_LINE_LENGTH - "X-Padding: ".len())
+            ),
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"));
+    }
+}
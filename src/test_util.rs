@@ -0,0 +1,56 @@
+use crate::engine::{OutputConfig, TransactionEngine};
+
+/// Process the given CSV input with the default config and return the output CSV,
+/// sorted by client id, just like the CLI writes it.
+///
+/// Surrounding whitespace and empty lines are ignored, so the input can be indented.
+/// This is meant for snapshot tests and panics if the input can't be processed.
+/// It runs its own runtime, so it must not be called from within an async context.
+///
+/// ```
+/// use tx_engine::test_util::run_csv;
+///
+/// let output = run_csv(
+///     "
+///     type,client,tx,amount
+///     deposit,1,1,10.0
+///     withdrawal,1,2,2.5
+///     ",
+/// );
+/// assert_eq!(
+///     output,
+///     "client,available,held,total,locked\n1,7.5000,0.0000,7.5000,false\n"
+/// );
+/// ```
+pub fn run_csv(input: &str) -> String {
+    let input: String = input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| format!("{}\n", line))
+        .collect();
+
+    // Process the records on a dedicated runtime
+    let engine = TransactionEngine::new();
+    tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Unable to build runtime.")
+        .block_on(engine.process_csv_bytes(input.as_bytes()))
+        .expect("Unable to process input.");
+
+    // Write the accounts like the CLI
+    let config = OutputConfig::default();
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer
+        .write_record(config.headers())
+        .expect("Unable to write headers.");
+    for account in engine.accounts().expect("Unable to read accounts.") {
+        writer
+            .serialize(account.output(&config))
+            .expect("Unable to write account.");
+    }
+    String::from_utf8(writer.into_inner().expect("Unable to write output."))
+        .expect("Output isn't valid UTF-8.")
+}